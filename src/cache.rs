@@ -0,0 +1,197 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, fs::{self, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, path::Path};
+
+use quote::ToTokens;
+
+use crate::oracle::Oracle;
+
+/// Remembers the outcome of testing each candidate, so that the same
+/// candidate is never tested twice.
+///
+/// Candidates are identified by a hash of their printed token stream. The
+/// outcomes may be persisted to a file, one line per candidate. The first
+/// line of the file identifies the oracle, the file is ignored if it was
+/// created for a different oracle.
+pub struct CachedOracle<O> {
+    inner: O,
+    results: HashMap<u128, bool>,
+    file: Option<fs::File>,
+    hits: usize,
+    misses: usize,
+}
+
+impl<O: Oracle> CachedOracle<O> {
+    pub fn new(inner: O) -> Self {
+        CachedOracle {
+            inner,
+            results: HashMap::new(),
+            file: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Load previous outcomes from `path`, and save new outcomes to it.
+    /// `oracle_id` should identify the oracle that determines the outcomes.
+    pub fn persist(&mut self, path: &Path, oracle_id: &str) -> io::Result<()> {
+        let header = format!("# {}", oracle_id.replace('\n', " "));
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        let mut lines = contents.split_inclusive(|&b| b == b'\n');
+        match lines.next() {
+            Some(line) if line.strip_suffix(b"\n") == Some(header.as_bytes()) => {
+                // a previous run may have been interrupted while writing the
+                // last entry, drop everything from the first invalid entry on
+                let mut valid_len = line.len();
+                for line in lines {
+                    match parse_entry(line) {
+                        Some((hash, interesting)) => self.results.insert(hash, interesting),
+                        None => {
+                            eprintln!("rust-reduce: ignoring invalid entries at the end of the cache file");
+                            break;
+                        },
+                    };
+                    valid_len += line.len();
+                }
+                file.set_len(valid_len as u64)?;
+                file.seek(SeekFrom::Start(valid_len as u64))?;
+            },
+            other => {
+                if other.is_some() {
+                    eprintln!("rust-reduce: ignoring cache file created for a different command");
+                }
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                writeln!(file, "{}", header)?;
+            },
+        }
+
+        self.file = Some(file);
+        Ok(())
+    }
+
+    pub fn inner(&self) -> &O {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut O {
+        &mut self.inner
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    fn record(&mut self, hash: u128, interesting: bool) {
+        self.results.insert(hash, interesting);
+        if let Some(file) = &mut self.file {
+            // write the entry at once, to keep an interrupted write short
+            let entry = format!("{:032x} {}\n", hash, interesting as u8);
+            if let Err(e) = file.write_all(entry.as_bytes()) {
+                eprintln!("rust-reduce: failed to write cache file: {}", e);
+                self.file = None;
+            }
+        }
+    }
+}
+
+impl<O: Oracle> Oracle for CachedOracle<O> {
+    fn jobs(&self) -> usize {
+        self.inner.jobs()
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        let hashes = candidates.iter().map(hash_file).collect::<Vec<_>>();
+
+        // only candidates before the first known interesting one need testing
+        let mut first_cached = None;
+        let mut untested = vec![];
+        for (i, hash) in hashes.iter().enumerate() {
+            match self.results.get(hash) {
+                Some(&interesting) => {
+                    self.hits += 1;
+                    if interesting {
+                        first_cached = Some(i);
+                        break;
+                    }
+                },
+                None => untested.push(i),
+            }
+        }
+
+        if untested.is_empty() {
+            return first_cached;
+        }
+
+        self.misses += untested.len();
+        let result = if untested.len() == candidates.len() {
+            self.inner.first_interesting(candidates)
+        } else {
+            let subset = untested.iter().map(|&i| candidates[i].clone()).collect::<Vec<_>>();
+            self.inner.first_interesting(&subset).map(|j| untested[j])
+        };
+
+        // the outcome of candidates after the first interesting one is unknown
+        for &i in &untested {
+            match result {
+                Some(first) if i > first => break,
+                Some(first) => self.record(hashes[i], i == first),
+                None => self.record(hashes[i], false),
+            }
+        }
+
+        result.or(first_cached)
+    }
+}
+
+/// Parses a `{hash} {0|1}` line, including the line terminator.
+fn parse_entry(line: &[u8]) -> Option<(u128, bool)> {
+    let line = std::str::from_utf8(line.strip_suffix(b"\n")?).ok()?;
+    let (hash, interesting) = line.split_once(' ')?;
+    if hash.len() != 32 {
+        return None;
+    }
+    let hash = u128::from_str_radix(hash, 16).ok()?;
+    match interesting {
+        "0" => Some((hash, false)),
+        "1" => Some((hash, true)),
+        _ => None,
+    }
+}
+
+/// 128-bit FNV-1a hash of the printed file, which is stable across runs.
+fn hash_file(file: &syn::File) -> u128 {
+    hash_bytes(file.into_token_stream().to_string().as_bytes())
+}
+
+/// 128-bit FNV-1a hash of `bytes`, which is stable across runs.
+pub fn hash_bytes(bytes: &[u8]) -> u128 {
+    const PRIME: u128 = 0x0000000001000000000000000000013B;
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+
+    bytes.iter().fold(OFFSET, |hash, &byte| {
+        (hash ^ byte as u128).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,180 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reduce the source of a Cargo package, testing candidates in copies of the
+//! package so that the manifest, features and build script are used.
+
+use std::{fs, io, path::{Path, PathBuf}};
+
+use quote::ToTokens;
+use tempfile::TempDir;
+use toml_edit::TomlError;
+
+use crate::{manifest, oracle::{self, FileTest, Oracle}, source::OriginalSource};
+
+/// The root module of the package in `dir`, `src/lib.rs` or `src/main.rs`.
+pub fn root_module(dir: &Path) -> Option<PathBuf> {
+    ["lib.rs", "main.rs"].iter()
+        .map(|name| dir.join("src").join(name))
+        .find(|path| path.is_file())
+}
+
+/// Writes each candidate to a copy of the package, and tests the copies
+/// concurrently. The test is passed the directory of the copy.
+pub struct PackageOracle<T> {
+    test: T,
+    source: OriginalSource,
+    /// The absolute path of the original package.
+    dir: PathBuf,
+    /// Where the directory of the root module is in each copy.
+    root_dir: PathBuf,
+    copies: Vec<TempDir>,
+    keep_formatting: bool,
+}
+
+impl<T: FileTest> PackageOracle<T> {
+    /// `source` must be created from the root module of the package in
+    /// `dir`. The package is copied once for each job, except for the
+    /// `target` directory. Relative paths of path dependencies are made
+    /// absolute in the copies. Packages that inherit from a workspace are not
+    /// supported, since the workspace isn't copied.
+    pub fn new(test: T, dir: &Path, source: OriginalSource, jobs: usize) -> io::Result<Self> {
+        let outside = || io::Error::new(io::ErrorKind::InvalidInput, "module files must be in the package directory");
+        let root_dir = source.root().parent()
+            .and_then(|root_dir| root_dir.strip_prefix(dir).ok())
+            .ok_or_else(outside)?
+            .to_owned();
+        if source.paths().any(|path| source.relative_path(path).is_none()) {
+            return Err(outside());
+        }
+
+        let manifest = fs::read_to_string(dir.join("Cargo.toml"))?;
+        if manifest::uses_workspace(&manifest).map_err(invalid_manifest)? {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cargo.toml inherits from a workspace (`workspace = true` or `package.workspace`), which isn't copied; replace the inherited keys with their values"));
+        }
+        let dir = fs::canonicalize(dir)?;
+        let manifest = manifest::absolute_paths(&manifest, &dir).map_err(invalid_manifest)?;
+
+        let copies = (0..jobs.max(1)).map(|_| {
+            let copy = tempfile::Builder::new().prefix("rust-reduce").tempdir()?;
+            copy_dir(&dir, copy.path(), &["target", ".git"])?;
+            fs::write(copy.path().join("Cargo.toml"), &manifest)?;
+            Ok(copy)
+        }).collect::<io::Result<_>>()?;
+
+        Ok(PackageOracle { test, source, dir, root_dir, copies, keep_formatting: false })
+    }
+
+    /// Write candidates using the original source text, instead of printing
+    /// their tokens.
+    pub fn keep_formatting(mut self) -> Self {
+        self.keep_formatting = true;
+        self
+    }
+
+    /// Test an unmodified copy of the package.
+    pub fn test_package(&self) -> bool {
+        self.test.test(self.copies[0].path())
+    }
+
+    /// Reduce `Cargo.toml`, with `file` as the source of the package. This
+    /// should be done after reducing the source, since the copies keep the
+    /// reduced manifest. The paths of path dependencies are kept as in the
+    /// original.
+    pub fn reduce_manifest(&mut self, file: &syn::File) -> io::Result<String> {
+        let manifest = fs::read_to_string(self.dir.join("Cargo.toml"))?;
+        manifest::reduce_manifest(&manifest, self.copies.len(), |candidates| {
+            self.first_interesting_package(candidates.len(), |i, copy| {
+                self.write_package(copy, file)?;
+                let candidate = manifest::absolute_paths(&candidates[i], &self.dir).map_err(invalid_manifest)?;
+                fs::write(copy.join("Cargo.toml"), candidate)
+            })
+        }).map_err(invalid_manifest)
+    }
+
+    /// Replace the module files in `copy` with those of `file`.
+    fn write_package(&self, copy: &Path, file: &syn::File) -> io::Result<()> {
+        let root_dir = copy.join(&self.root_dir);
+        for path in self.source.paths() {
+            match fs::remove_file(root_dir.join(self.source.relative_path(path).expect("checked in `new`"))) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {},
+            }
+        }
+        for (path, file) in self.source.module_files(file) {
+            let text = if self.keep_formatting {
+                self.source.print(&file)
+            } else {
+                file.into_token_stream().to_string()
+            };
+            let path = root_dir.join(self.source.relative_path(path).expect("checked in `new`"));
+            fs::create_dir_all(path.parent().expect("in the copy"))?;
+            fs::write(path, text)?;
+        }
+        Ok(())
+    }
+
+    /// Test `count` candidates, using `write` to write each candidate to a
+    /// copy, and return the index of the first interesting one.
+    fn first_interesting_package<W>(&self, count: usize, write: W) -> Option<usize>
+        where W: Fn(usize, &Path) -> io::Result<()>
+    {
+        for start in (0..count).step_by(self.copies.len()) {
+            let written = (start..count).zip(&self.copies)
+                .map(|(i, copy)| write(i, copy.path()).ok().map(|_| copy.path()))
+                .collect::<Vec<_>>();
+            if let Some(i) = oracle::first_interesting_path(&self.test, &written) {
+                return Some(start + i);
+            }
+        }
+        None
+    }
+}
+
+impl<T: FileTest> Oracle for PackageOracle<T> {
+    fn jobs(&self) -> usize {
+        self.copies.len()
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        self.first_interesting_package(candidates.len(), |i, copy| self.write_package(copy, &candidates[i]))
+    }
+}
+
+fn invalid_manifest(e: TomlError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Copy the contents of `from` to `to`, except for the entries named in
+/// `skip`.
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|skip| name == *skip) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            fs::create_dir_all(to.join(&name))?;
+            copy_dir(&path, &to.join(&name), &[])?;
+        } else if path.is_file() {
+            fs::copy(&path, to.join(&name))?;
+        }
+    }
+    Ok(())
+}
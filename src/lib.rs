@@ -0,0 +1,147 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reduce Rust source files, keeping them interesting according to an
+//! [`Oracle`].
+//!
+//! ```no_run
+//! let file = rust_reduce::inline_modules("src/lib.rs").unwrap();
+//! let reduced = rust_reduce::Reducer::new(file)
+//!     .reduce(&mut |file: &syn::File| is_interesting(file));
+//! # fn is_interesting(_: &syn::File) -> bool { true }
+//! ```
+
+use std::path::Path;
+
+use syn_inline_mod::InlinerBuilder;
+
+pub use syn_inline_mod::Error as InlineError;
+
+use crate::oracle::Oracle;
+use crate::transforms::Transform;
+
+pub mod cache;
+pub mod cargo;
+pub mod manifest;
+pub mod oracle;
+pub mod rustc;
+pub mod source;
+pub mod transforms;
+pub mod vendor;
+
+/// Parse the file at `path`, replacing each `mod foo;` declaration with the
+/// contents of the corresponding file. Fails if a module file is missing.
+pub fn inline_modules<P: AsRef<Path>>(path: P) -> Result<syn::File, InlineError> {
+    InlinerBuilder::new()
+        .error_not_found(true)
+        .parse_and_inline_modules(path.as_ref())
+}
+
+/// Runs reduction passes on a file.
+///
+/// All passes are repeated until a full round of passes doesn't find any more
+/// reductions, because a reduction found by a later pass may enable further
+/// reductions by an earlier pass.
+pub struct Reducer {
+    file: syn::File,
+    passes: Vec<&'static dyn Transform>,
+    max_rounds: Option<usize>,
+    verbose: bool,
+}
+
+impl Reducer {
+    /// A reducer that runs all passes in [`transforms::ALL`].
+    pub fn new(file: syn::File) -> Self {
+        Reducer {
+            file,
+            passes: transforms::ALL.to_vec(),
+            max_rounds: None,
+            verbose: false,
+        }
+    }
+
+    /// The passes to run in each round, in order.
+    pub fn passes<I: IntoIterator<Item = &'static dyn Transform>>(mut self, passes: I) -> Self {
+        self.passes = passes.into_iter().collect();
+        self
+    }
+
+    /// Stop after this many rounds, even if the last round found reductions.
+    pub fn max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = Some(max_rounds);
+        self
+    }
+
+    /// Print progress to stderr.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Reduce the file, keeping only changes that `oracle` finds interesting.
+    /// The file itself is assumed to be interesting.
+    pub fn reduce<O: Oracle + ?Sized>(self, oracle: &mut O) -> syn::File {
+        let Reducer { mut file, passes, max_rounds, verbose } = self;
+        let mut tracker = ChangeTracker { oracle, changed: false };
+
+        let mut round = 0;
+        loop {
+            round += 1;
+            if verbose {
+                eprintln!("Round {}", round);
+            }
+            tracker.changed = false;
+
+            for pass in &passes {
+                if verbose {
+                    eprintln!("Running pass {}", pass.name());
+                }
+                pass.run(&mut file, &mut tracker);
+            }
+
+            if !tracker.changed {
+                break;
+            }
+            if max_rounds.is_some_and(|max| round >= max) {
+                if verbose {
+                    eprintln!("Stopping after round {}", round);
+                }
+                break;
+            }
+        }
+
+        file
+    }
+}
+
+/// Keeps track of whether any interesting reductions were found.
+struct ChangeTracker<'a, O: ?Sized> {
+    oracle: &'a mut O,
+    changed: bool,
+}
+
+impl<O: Oracle + ?Sized> Oracle for ChangeTracker<'_, O> {
+    fn jobs(&self) -> usize {
+        self.oracle.jobs()
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        let result = self.oracle.first_interesting(candidates);
+        self.changed |= result.is_some();
+        result
+    }
+}
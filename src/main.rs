@@ -15,50 +15,186 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{ffi::OsString, io::Write, process::{Command, Stdio}};
+use std::{fs, io::Write, path::PathBuf, time::Duration};
 
-use clap::clap_app;
+use clap::{clap_app, value_t};
 use quote::ToTokens;
-use syn_inline_mod::{Error as InlineError, InlinerBuilder};
-use tempfile::NamedTempFile;
+
+use regex::Regex;
+use rust_reduce::{cache::{self, CachedOracle}, cargo::{self, PackageOracle}, oracle::{self, CommandTest, FileOracle, FileTest, Oracle}, rustc::{DifferentialTest, Matcher, Rustc, RustcTest}, source::OriginalSource, transforms, vendor, InlineError, Reducer};
 
 mod output;
-mod transforms;
 
 fn main() {
     let matches = clap_app!(("rust-reduce") =>
         (version: clap::crate_version!())
-        (@arg CMD: * "Command to run.")
-        (@arg ARGS: * ... "Arguments to the command to run.
+        (@arg CMD: required_unless[LIST_PASSES] "Command to run, or the file of interest when using a built-in oracle.")
+        (@arg ARGS: required_unless_one(&["LIST_PASSES", "RUSTC_ERROR", "STDERR_REGEX", "ICE", "DIFFERENTIAL", "CARGO"]) conflicts_with[RUSTC_ERROR STDERR_REGEX ICE DIFFERENTIAL] ... "Arguments to the command to run.
         
 The last argument must be the path of the existing file of interest. CMD will be invoked with the last argument replaced with the path to a temporary file.
 
 You can use `--` to separate ARGS from any arguments passed to `rust-reduce`.")
+        (@arg CARGO: --cargo +takes_value conflicts_with[RUSTC_ERROR STDERR_REGEX ICE DIFFERENTIAL] "Reduce the module tree of src/lib.rs or src/main.rs of the Cargo package in this directory. Each candidate is written to a copy of the package, and CMD ARGS is run in the copy, e.g. `cargo check`. The files of the package are replaced with the reduction, like with --module-tree. Afterwards, Cargo.toml is reduced as well. Only the package directory is copied: relative path dependencies are made absolute in the copies, but packages that inherit from their workspace with `workspace = true` aren't supported.")
+        (@arg KEEP_MANIFEST: --("keep-manifest") requires[CARGO] "Don't reduce Cargo.toml after reducing the source of the package. Required with --output, since the reduced Cargo.toml is written to the package.")
+        (@arg VENDOR: --vendor +takes_value +multiple number_of_values(1) {vendor_spec} "Add the source of a dependency to the input as a module, given as NAME=PATH where PATH is the directory of the dependency, e.g. from `cargo vendor`. `extern crate` items and paths that refer to the dependency are rewritten to refer to the module. May be specified multiple times.")
         (@arg FILE: -o --output +takes_value "Reduced output file (default is to replace input file).")
         (@arg ONCE: short("1") --("no-progress") "Only save the fully reduced output, not the intermediates.")
+        (@arg KEEP_FORMATTING: --("keep-formatting") "Keep the original formatting and comments of the remaining code, both in the output and in the files passed to CMD. rustfmt is not used.")
+        (@arg MODULE_TREE: --("module-tree") "Write the output as one file per module, like the original files, instead of inlining all modules into a single file. Files of removed modules are deleted.")
+        (@arg JOBS: -j --jobs +takes_value {positive_integer} "Number of candidate reductions to test concurrently (default 1). The result does not depend on the number of jobs.")
+        (@arg TIMEOUT: --timeout +takes_value {positive_number} "Kill the command if it runs longer than this many seconds, and consider the reduction uninteresting.")
+        (@arg TIMEOUT_INTERESTING: --("timeout-is-interesting") requires[TIMEOUT] conflicts_with[DIFFERENTIAL] "Consider a reduction interesting if the command times out, e.g. when reducing a hang.")
+        (@arg CACHE: --cache +takes_value "File to save the outcome of each test in. If the file exists, known outcomes are not tested again.")
+        (@arg MAX_ROUNDS: --("max-rounds") +takes_value {positive_integer} "Maximum number of times to run all passes (default is to repeat until no more reductions are found).")
+        (@arg PASSES: --passes +takes_value +use_delimiter {pass_name} "Comma-separated list of passes to run, in order (default is all passes).")
+        (@arg SKIP_PASS: --("skip-pass") +takes_value +multiple number_of_values(1) {pass_name} "Don't run this pass. May be specified multiple times.")
+        (@arg LIST_PASSES: --("list-passes") "List the available passes and exit.")
+        (@group ORACLE =>
+            (@arg RUSTC_ERROR: --("rustc-error") +takes_value {error_code} "Built-in oracle: compile with rustc, interesting if compilation fails with this error code, e.g. E0277.")
+            (@arg STDERR_REGEX: --("stderr-regex") +takes_value {regex} "Built-in oracle: compile with rustc, interesting if the compiler output matches this regular expression.")
+            (@arg ICE: --ice "Built-in oracle: compile with rustc, interesting if the compiler crashes with the same internal compiler error as the initial input. The panic message and the top of the query stack must match.")
+            (@arg DIFFERENTIAL: --differential +takes_value number_of_values(2) +allow_hyphen_values value_names(&["FLAGS_A", "FLAGS_B"]) "Built-in oracle: compile and run the program with two sets of space-separated rustc flags, interesting if the programs behave differently. The flags may start with a `+toolchain` override. Each compilation and run is limited by --timeout (default 10 seconds).")
+        )
+        (@arg ICE_FRAMES: --("ice-frames") +takes_value requires[ICE] "Number of query stack frames that must match for --ice (default 3).")
+        (@arg RUSTC: --rustc +takes_value requires[ORACLE] "The rustc to run for built-in oracles (default `rustc`).")
+        (@arg RUSTC_FLAGS: --("rustc-flags") +takes_value +allow_hyphen_values requires[ORACLE] "Space-separated flags to pass to rustc for built-in oracles, e.g. `--crate-type lib`.")
+        (@arg EDITION: --edition +takes_value possible_values(&["2015", "2018", "2021", "2024"]) requires[ORACLE] "The edition to pass to rustc for built-in oracles (default is rustc's default).")
         (after_help: "\
 `rust-reduce` will try to make the source file smaller by interpreting it as valid Rust code and intelligently removing parts of the code. After each removal, the given command will be run but passing a path to a file containing the reduced code. The command should return 0 if run on the original input, and also if the reduced code is interesting, non-0 otherwise.
 
 The original file will be overwritten with the smallest interesting reduced version, if found. This happens while `rust-reduce` is running. The original file will be backed up with the `.orig` suffix. If `rustfmt` is found, it will be used to clean up the output.
 
-A common way to use `rust-reduce` is to write a short shell script that runs `rustc` and greps the compiler output for a particular error message. NB. you will want to look for a specific error message because while `rust-reduce` will generate syntactically correct code, it's not guaranteed to compile.
+A common way to use `rust-reduce` is to write a short shell script that runs `rustc` and greps the compiler output for a particular error message. NB. you will want to look for a specific error message because while `rust-reduce` will generate syntactically correct code, it's not guaranteed to compile. For common cases, the built-in oracles `--rustc-error`, `--stderr-regex` and `--ice` can be used instead of a command, in which case the only positional argument is the file of interest.
+
+The original file may refer to modules in different files, these will be inlined and reduced along with the main file.
 
-The original file may refer to modules in different files, these will be inlined and reduced along with the main file.")
+All passes are repeated until a full round of passes doesn't find any more reductions, because a reduction found by a later pass may enable further reductions by an earlier pass.")
     ).get_matches();
 
-    let mut cmd = vec![matches.value_of_os("CMD").expect("validated").to_owned()];
-    let mut iter = matches.values_of_os("ARGS").expect("validated").map(ToOwned::to_owned);
-    let file = iter.next_back().expect("validated");
-    cmd.extend(iter);
+    if matches.is_present("LIST_PASSES") {
+        for pass in transforms::ALL {
+            println!("{:<20} {}", pass.name(), pass.description());
+        }
+        return;
+    }
+
+    let passes = match matches.values_of("PASSES") {
+        Some(names) => names.map(|name| transforms::by_name(name).expect("validated")).collect(),
+        None => transforms::ALL.to_vec(),
+    };
+    let skip = matches.values_of("SKIP_PASS").map(|names| names.collect::<Vec<_>>()).unwrap_or_default();
+    let passes = passes.into_iter().filter(|pass| !skip.contains(&pass.name())).collect::<Vec<_>>();
+
+    let jobs = if matches.is_present("JOBS") {
+        value_t!(matches, "JOBS", usize).unwrap_or_else(|e| e.exit())
+    } else {
+        1
+    };
+    let timeout = if matches.is_present("TIMEOUT") {
+        let secs = value_t!(matches, "TIMEOUT", f64).unwrap_or_else(|e| e.exit());
+        Some(oracle::Timeout {
+            duration: Duration::from_secs_f64(secs),
+            interesting: matches.is_present("TIMEOUT_INTERESTING"),
+        })
+    } else {
+        None
+    };
+    let max_rounds = if matches.is_present("MAX_ROUNDS") {
+        Some(value_t!(matches, "MAX_ROUNDS", usize).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+
+    let matcher = if let Some(code) = matches.value_of("RUSTC_ERROR") {
+        Some(Matcher::ErrorCode(code.to_owned()))
+    } else if let Some(re) = matches.value_of("STDERR_REGEX") {
+        Some(Matcher::StderrRegex(Regex::new(re).expect("validated")))
+    } else if matches.is_present("ICE") {
+        Some(Matcher::Ice)
+    } else {
+        None
+    };
 
-    if !run_with_path(&cmd, &file) {
+    let mut rustc = Rustc::default()
+        .flags(matches.value_of("RUSTC_FLAGS").unwrap_or_default().split_whitespace());
+    if let Some(path) = matches.value_of_os("RUSTC") {
+        rustc = rustc.rustc(path);
+    }
+    if let Some(edition) = matches.value_of("EDITION") {
+        rustc = rustc.edition(edition);
+    }
+
+    let package = matches.value_of_os("CARGO").map(PathBuf::from);
+    if package.is_some() && matches.is_present("FILE") && !matches.is_present("KEEP_MANIFEST") {
+        eprintln!("rust-reduce: --cargo with --output requires --keep-manifest, since the reduced Cargo.toml replaces the one in the package");
+        std::process::exit(1);
+    }
+
+    let (file, test, oracle_id): (_, Box<dyn FileTest>, _) = match matcher {
+        None if package.is_some() => {
+            let dir = package.as_ref().expect("checked above");
+            let file = match cargo::root_module(dir) {
+                Some(file) => file.into_os_string(),
+                None => {
+                    eprintln!("rust-reduce: no src/lib.rs or src/main.rs in {}", dir.display());
+                    std::process::exit(1);
+                },
+            };
+            let mut cmd = PathBuf::from(matches.value_of_os("CMD").expect("validated"));
+            // the command is run in the copy of the package
+            if cmd.is_relative() && cmd.components().count() > 1 {
+                cmd = std::env::current_dir().expect("current directory").join(cmd);
+            }
+            let mut cmd = vec![cmd.into_os_string()];
+            cmd.extend(matches.values_of_os("ARGS").into_iter().flatten().map(ToOwned::to_owned));
+            let cmd_line = cmd.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" ");
+            (file, Box::new(CommandTest::new(cmd, timeout).in_dir()), format!("{} (cargo)", cmd_line))
+        },
+        None if matches.is_present("DIFFERENTIAL") => {
+            let file = matches.value_of_os("CMD").expect("validated").to_owned();
+            let mut configs = matches.values_of("DIFFERENTIAL").expect("validated").map(str::split_whitespace);
+            let (a, b) = (configs.next().expect("validated"), configs.next().expect("validated"));
+            let timeout = timeout.map_or(Duration::from_secs(10), |timeout| timeout.duration);
+            let test = DifferentialTest::new(rustc, a, b, timeout);
+            let oracle_id = test.describe();
+            (file, Box::new(test), oracle_id)
+        },
+        Some(matcher) => {
+            let file = matches.value_of_os("CMD").expect("validated").to_owned();
+            let mut test = RustcTest::new(rustc, matcher);
+            if let Some(timeout) = timeout {
+                test = test.timeout(timeout);
+            }
+            if matches.is_present("ICE") {
+                let frames = if matches.is_present("ICE_FRAMES") {
+                    value_t!(matches, "ICE_FRAMES", usize).unwrap_or_else(|e| e.exit())
+                } else {
+                    3
+                };
+                if !test.require_ice_signature(file.as_ref(), frames) {
+                    eprintln!("rust-reduce: initial input did not cause an internal compiler error");
+                    std::process::exit(1);
+                }
+            }
+            let oracle_id = test.describe();
+            (file, Box::new(test), oracle_id)
+        },
+        None => {
+            let mut cmd = vec![matches.value_of_os("CMD").expect("validated").to_owned()];
+            let mut iter = matches.values_of_os("ARGS").expect("validated").map(ToOwned::to_owned);
+            let file = iter.next_back().expect("validated");
+            cmd.extend(iter);
+            let cmd_line = cmd.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" ");
+            (file, Box::new(CommandTest::new(cmd, timeout)), cmd_line)
+        },
+    };
+    // packages are tested once they're copied
+    if package.is_none() && !test.test(file.as_ref()) {
         eprintln!("rust-reduce: run with initial input did not indicate success");
         std::process::exit(1);
     }
 
-    let mut inlined_file = match InlinerBuilder::new()
-        .error_not_found(true)
-        .parse_and_inline_modules(file.as_ref()) {
+    let mut inlined_file = match rust_reduce::inline_modules(&file) {
         Ok(f) => f,
         Err(InlineError::NotFound(missing)) => {
             eprintln!("rust-reduce: file not found");
@@ -69,50 +205,239 @@ The original file may refer to modules in different files, these will be inlined
         },
         Err(_) => unimplemented!()
     };
+    for spec in matches.values_of("VENDOR").into_iter().flatten() {
+        let (name, path) = spec.split_once('=').expect("validated");
+        if let Err(e) = vendor::vendor(&mut inlined_file, name, path.as_ref()) {
+            eprintln!("rust-reduce: failed to vendor {}: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+    let keep_formatting = matches.is_present("KEEP_FORMATTING");
+    let module_tree = matches.is_present("MODULE_TREE") || package.is_some();
+    let source = if keep_formatting || module_tree {
+        match OriginalSource::new(file.as_ref(), &inlined_file) {
+            Ok(source) => Some(source),
+            Err(e) => {
+                eprintln!("rust-reduce: failed to read source file: {}", e);
+                std::process::exit(1);
+            },
+        }
+    } else {
+        None
+    };
+
+    let output_path = matches.value_of_os("FILE").map(ToOwned::to_owned).unwrap_or_else(|| file.clone());
+    if let (Some(source), true) = (&source, module_tree) {
+        if output_path == "-" {
+            eprintln!("rust-reduce: --module-tree can't write to standard output");
+            std::process::exit(1);
+        }
+        if let Some(path) = source.paths().find(|path| source.relative_path(path).is_none()) {
+            eprintln!("rust-reduce: --module-tree can't write module file outside the directory of the root file: {}", path.display());
+            std::process::exit(1);
+        }
+    }
+    // only used to print files if keeping the formatting
+    let formatting = source.as_ref().filter(|_| keep_formatting);
+
+    let mut oracle_id = oracle_id;
+    if keep_formatting {
+        oracle_id.push_str(" (keep formatting)");
+    }
+    if let Some(timeout) = timeout {
+        oracle_id.push_str(&format!(" (timeout {}s", timeout.duration.as_secs_f64()));
+        oracle_id.push_str(if timeout.interesting { ", interesting)" } else { ")" });
+    }
+    if let Some(dir) = &package {
+        // the outcome also depends on the rest of the package
+        let manifest = fs::read(dir.join("Cargo.toml")).unwrap_or_default();
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.clone());
+        oracle_id.push_str(&format!(" (package {}, Cargo.toml {:032x})", dir.display(), cache::hash_bytes(&manifest)));
+    }
+    let inner = match &package {
+        Some(dir) => {
+            let source = source.clone().expect("always read for packages");
+            let mut package_oracle = match PackageOracle::new(test, dir, source, jobs) {
+                Ok(package_oracle) => package_oracle,
+                Err(e) => {
+                    eprintln!("rust-reduce: failed to copy package: {}", e);
+                    std::process::exit(1);
+                },
+            };
+            if keep_formatting {
+                package_oracle = package_oracle.keep_formatting();
+            }
+            if !package_oracle.test_package() {
+                eprintln!("rust-reduce: run with initial input did not indicate success");
+                std::process::exit(1);
+            }
+            MainOracle::Package(package_oracle)
+        },
+        None => {
+            let mut file_oracle = FileOracle::new(test, jobs);
+            if let Some(source) = formatting {
+                file_oracle = file_oracle.keep_formatting(source.clone());
+            }
+            MainOracle::File(file_oracle)
+        },
+    };
+    let mut oracle = CachedOracle::new(inner);
+    if let Some(path) = matches.value_of_os("CACHE") {
+        if let Err(e) = oracle.persist(path.as_ref(), &oracle_id) {
+            eprintln!("rust-reduce: failed to open cache file: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // the passes operate on the inlined file, which isn't necessarily
+    // equivalent to the original
+    if oracle.first_interesting(std::slice::from_ref(&inlined_file)).is_none() {
+        eprintln!("rust-reduce: initial input is interesting, but not after inlining modules and printing it");
+        eprintln!("    This can happen if the input depends on comments, formatting or source locations,");
+        eprintln!("    on `#[path]` attributes, or on macro hygiene.");
+        if matches.is_present("VENDOR") {
+            eprintln!("    The dependencies added with --vendor may also behave differently as modules.");
+        }
+        if formatting.is_none() {
+            eprintln!("    Try --keep-formatting to keep the original formatting and comments.");
+        }
+        let printed = match formatting {
+            Some(source) => source.print(&inlined_file),
+            None => inlined_file.into_token_stream().to_string(),
+        };
+        let saved = tempfile::Builder::new()
+            .prefix("rust-reduce-inlined")
+            .suffix(".rs")
+            .tempfile()
+            .and_then(|mut f| f.write_all(printed.as_bytes()).map(|_| f))
+            .and_then(|f| f.keep().map_err(|e| e.error));
+        if let Ok((_, path)) = saved {
+            eprintln!("    The inlined input was saved to {}", path.display());
+        }
+        std::process::exit(1);
+    }
     let mut output = (if matches.is_present("ONCE") {
         output::WaitGuard::new::<output::LastWriter, _>
     } else {
         output::WaitGuard::new::<output::AsyncWriter, _>
     })(
-        matches.value_of_os("FILE").map(ToOwned::to_owned).unwrap_or(file),
+        output_path.clone(),
         !matches.is_present("FILE")
     );
+    if let Some(source) = source {
+        if keep_formatting {
+            output.keep_formatting(source.clone());
+        }
+        if module_tree {
+            output.module_tree(source);
+        }
+    }
 
-    let mut try_compile = |reduced_file: &_| {
-        let result = run_with_path(&cmd, &write_file(reduced_file).path());
+    let mut progress = Progress { oracle, output };
+    let mut reducer = Reducer::new(inlined_file).passes(passes).verbose(true);
+    if let Some(max_rounds) = max_rounds {
+        reducer = reducer.max_rounds(max_rounds);
+    }
+    let reduced_file = reducer.reduce(&mut progress);
 
-        if result {
-            output.output_formatted(reduced_file)
+    if let (Some(dir), MainOracle::Package(package_oracle)) = (&package, progress.oracle.inner_mut()) {
+        if !matches.is_present("KEEP_MANIFEST") {
+            eprintln!("Reducing Cargo.toml");
+            let manifest_path = dir.join("Cargo.toml");
+            let result = package_oracle.reduce_manifest(&reduced_file).and_then(|manifest| {
+                fs::copy(&manifest_path, dir.join("Cargo.toml.orig"))?;
+                fs::write(&manifest_path, manifest)
+            });
+            if let Err(e) = result {
+                eprintln!("rust-reduce: failed to reduce Cargo.toml: {}", e);
+                std::process::exit(1);
+            }
         }
+    }
 
-        result
-    };
+    eprintln!("Ran {} tests, {} cache hits", progress.oracle.misses(), progress.oracle.hits());
+}
+
+fn pass_name(v: String) -> Result<(), String> {
+    match transforms::by_name(&v) {
+        Some(_) => Ok(()),
+        None => Err(format!("unknown pass `{}`, use --list-passes to see all passes", v)),
+    }
+}
+
+fn error_code(v: String) -> Result<(), String> {
+    if v.len() == 5 && v.starts_with('E') && v[1..].bytes().all(|b| b.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(String::from("expected an error code like E0277"))
+    }
+}
+
+fn regex(v: String) -> Result<(), String> {
+    Regex::new(&v).map(|_| ()).map_err(|e| e.to_string())
+}
 
-    eprintln!("Pruning items");
-    transforms::prune_items::prune_items(&mut inlined_file, &mut try_compile);
-    eprintln!("Removing #[doc] attributes");
-    transforms::remove_doc_attrs::remove_doc_attrs(&mut inlined_file, &mut try_compile);
-    eprintln!("Clearing block bodies");
-    transforms::clear_blocks::clear_blocks(&mut inlined_file, &mut try_compile);
+fn vendor_spec(v: String) -> Result<(), String> {
+    match v.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok(()),
+        _ => Err(String::from("expected NAME=PATH")),
+    }
+}
+
+fn positive_integer(v: String) -> Result<(), String> {
+    match v.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(String::from("expected a positive integer")),
+    }
 }
 
-fn run_with_path<P: AsRef<std::path::Path>>(cmd: &[OsString], path: &P) -> bool {
-    let (cmd, args) = cmd.split_first().expect("validated");
-    match Command::new(cmd)
-        .args(args)
-        .arg(path.as_ref())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-    {
-        Ok(ref stat) if stat.success() => true,
-        _ => false
+fn positive_number(v: String) -> Result<(), String> {
+    match v.parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(()),
+        _ => Err(String::from("expected a positive number")),
     }
 }
 
-fn write_file(contents: &syn::File) -> NamedTempFile {
-    let mut file = tempfile::Builder::new().prefix("test").tempfile().unwrap();
-    write!(file, "{}", contents.into_token_stream()).unwrap();
-    file.flush().unwrap();
-    file
+/// Tests single files, or copies of a Cargo package.
+enum MainOracle {
+    File(FileOracle<Box<dyn FileTest>>),
+    Package(PackageOracle<Box<dyn FileTest>>),
+}
+
+impl Oracle for MainOracle {
+    fn jobs(&self) -> usize {
+        match self {
+            MainOracle::File(oracle) => oracle.jobs(),
+            MainOracle::Package(oracle) => oracle.jobs(),
+        }
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        match self {
+            MainOracle::File(oracle) => oracle.first_interesting(candidates),
+            MainOracle::Package(oracle) => oracle.first_interesting(candidates),
+        }
+    }
+}
+
+/// Outputs each interesting reduction.
+struct Progress {
+    oracle: CachedOracle<MainOracle>,
+    output: output::WaitGuard,
+}
+
+impl Oracle for Progress {
+    fn jobs(&self) -> usize {
+        self.oracle.jobs()
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        let result = self.oracle.first_interesting(candidates);
+
+        if let Some(i) = result {
+            self.output.output_formatted(&candidates[i]);
+        }
+
+        result
+    }
 }
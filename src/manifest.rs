@@ -0,0 +1,216 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each dependency, each enabled feature, each feature
+//! definition and each profile setting from a Cargo manifest.
+//!
+//! Also prepares the manifest for use in a copy of the package.
+
+use std::path::Path;
+
+use toml_edit::{value, DocumentMut, Item, TomlError};
+
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Something that can be removed from the manifest.
+enum Edit {
+    /// Remove the key at the end of this path.
+    Key(Vec<String>),
+    /// Remove an element of the array at this path.
+    ArrayElement(Vec<String>, usize),
+}
+
+/// Reduce `manifest`. `first_interesting` returns the index of the first
+/// interesting candidate manifest, if any. At most `jobs` candidates are
+/// tested at once.
+///
+/// All edits are repeated until none of them is interesting anymore, since
+/// e.g. an optional dependency can only be removed after the features that
+/// enable it.
+pub fn reduce_manifest<F>(manifest: &str, jobs: usize, mut first_interesting: F) -> Result<String, TomlError>
+    where F: FnMut(&[String]) -> Option<usize>
+{
+    let mut doc = manifest.parse::<DocumentMut>()?;
+    let mut index = 0;
+    let mut changed = false;
+    loop {
+        let edits = edits(&doc);
+        let candidates = edits.iter().skip(index).take(jobs.max(1))
+            .map(|edit| {
+                let mut candidate = doc.clone();
+                apply(&mut candidate, edit);
+                candidate
+            })
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            // no more changes to be made
+            if !changed {
+                return Ok(doc.to_string());
+            }
+            index = 0;
+            changed = false;
+            continue;
+        }
+
+        let texts = candidates.iter().map(ToString::to_string).collect::<Vec<_>>();
+        match first_interesting(&texts) {
+            // the next edit will be at the same index now that something was
+            // removed
+            Some(i) => {
+                doc = candidates.into_iter().nth(i).expect("valid index");
+                index += i;
+                changed = true;
+            },
+            None => index += candidates.len(),
+        }
+    }
+}
+
+/// Make the `path` of each path dependency in `manifest` absolute, relative
+/// to `dir`, so that the manifest can be used in a copy of the package.
+pub fn absolute_paths(manifest: &str, dir: &Path) -> Result<String, TomlError> {
+    let mut doc = manifest.parse::<DocumentMut>()?;
+    let mut tables = dependency_tables(&doc);
+    if let Some(patches) = doc.get("patch").and_then(Item::as_table_like) {
+        tables.extend(patches.iter().map(|(source, _)| vec!["patch".into(), source.into()]));
+    }
+    for table in &tables {
+        if let Some(table_like) = get_mut(&mut doc, table).and_then(Item::as_table_like_mut) {
+            for (_, dependency) in table_like.iter_mut() {
+                let path = match dependency.get_mut("path") {
+                    Some(path) => path,
+                    None => continue,
+                };
+                if let Some(absolute) = path.as_str().map(|p| dir.join(p)) {
+                    *path = value(absolute.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Whether `manifest` inherits anything from a workspace, with
+/// `workspace = true` keys or `package.workspace`.
+pub fn uses_workspace(manifest: &str) -> Result<bool, TomlError> {
+    fn inherits(item: &Item) -> bool {
+        match item.as_table_like() {
+            Some(table_like) => table_like.iter().any(|(key, item)| {
+                (key == "workspace" && item.as_bool() == Some(true)) || inherits(item)
+            }),
+            None => false,
+        }
+    }
+
+    let doc = manifest.parse::<DocumentMut>()?;
+    Ok(inherits(doc.as_item()) || doc.get("package").and_then(|package| package.get("workspace")).is_some())
+}
+
+/// The paths of all dependency tables in `doc`, including target-specific
+/// ones.
+fn dependency_tables(doc: &DocumentMut) -> Vec<Vec<String>> {
+    let mut dependency_tables = DEPENDENCY_TABLES.iter()
+        .map(|table| vec![table.to_string()])
+        .collect::<Vec<_>>();
+    if let Some(targets) = doc.get("target").and_then(Item::as_table_like) {
+        for (target, item) in targets.iter() {
+            for table in DEPENDENCY_TABLES {
+                if item.get(table).is_some() {
+                    dependency_tables.push(vec!["target".into(), target.into(), table.to_string()]);
+                }
+            }
+        }
+    }
+    dependency_tables
+}
+
+/// All edits that can be made to `doc`, in order.
+fn edits(doc: &DocumentMut) -> Vec<Edit> {
+    let mut dependencies = vec![];
+    for table in &dependency_tables(doc) {
+        if let Some(table_like) = get(doc, table).and_then(Item::as_table_like) {
+            dependencies.extend(table_like.iter().map(|(name, _)| with(table, name)));
+        }
+    }
+
+    let mut edits = dependencies.iter().cloned().map(Edit::Key).collect::<Vec<_>>();
+    for dependency in &dependencies {
+        let features = with(dependency, "features");
+        if let Some(array) = get(doc, &features).and_then(Item::as_array) {
+            edits.extend((0..array.len()).map(|i| Edit::ArrayElement(features.clone(), i)));
+        }
+    }
+    if let Some(features) = doc.get("features").and_then(Item::as_table_like) {
+        // first the features enabled by each feature, so that the features
+        // that are no longer enabled can be removed afterwards
+        for (name, item) in features.iter() {
+            if let Some(array) = item.as_array() {
+                edits.extend((0..array.len()).map(|i| Edit::ArrayElement(vec!["features".into(), name.into()], i)));
+            }
+        }
+        edits.extend(features.iter().map(|(name, _)| Edit::Key(vec!["features".into(), name.into()])));
+    }
+    if let Some(profiles) = doc.get("profile").and_then(Item::as_table_like) {
+        for (profile, item) in profiles.iter() {
+            if let Some(settings) = item.as_table_like() {
+                edits.extend(settings.iter().map(|(name, _)| Edit::Key(vec!["profile".into(), profile.into(), name.into()])));
+            }
+        }
+    }
+    edits
+}
+
+fn apply(doc: &mut DocumentMut, edit: &Edit) {
+    match edit {
+        Edit::Key(path) => {
+            let (key, parent) = path.split_last().expect("non-empty path");
+            if let Some(table_like) = get_mut(doc, parent).and_then(Item::as_table_like_mut) {
+                table_like.remove(key);
+            }
+            // don't leave empty tables behind, like `[profile.dev]`
+            for len in (1..path.len()).rev() {
+                let (key, parent) = path[..len].split_last().expect("non-empty path");
+                if !get(doc, &path[..len]).and_then(Item::as_table_like).is_some_and(|table_like| table_like.is_empty()) {
+                    break;
+                }
+                if let Some(table_like) = get_mut(doc, parent).and_then(Item::as_table_like_mut) {
+                    table_like.remove(key);
+                }
+            }
+        },
+        Edit::ArrayElement(path, i) => {
+            if let Some(array) = get_mut(doc, path).and_then(Item::as_array_mut) {
+                array.remove(*i);
+            }
+        },
+    }
+}
+
+fn get<'a>(doc: &'a DocumentMut, path: &[String]) -> Option<&'a Item> {
+    path.iter().try_fold(doc.as_item(), |item, key| item.get(key))
+}
+
+fn get_mut<'a>(doc: &'a mut DocumentMut, path: &[String]) -> Option<&'a mut Item> {
+    path.iter().try_fold(doc.as_item_mut(), |item, key| item.get_mut(key))
+}
+
+fn with(path: &[String], key: &str) -> Vec<String> {
+    let mut path = path.to_owned();
+    path.push(key.to_owned());
+    path
+}
@@ -0,0 +1,227 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{ffi::OsString, io::{self, Write}, path::Path, process::{Child, Command, ExitStatus, Stdio}, thread, time::{Duration, Instant}};
+
+use quote::ToTokens;
+use tempfile::NamedTempFile;
+
+use crate::source::OriginalSource;
+
+/// Decides whether reduced files are still interesting.
+pub trait Oracle {
+    /// The number of candidates that can be tested concurrently.
+    fn jobs(&self) -> usize {
+        1
+    }
+
+    /// Returns the index of the first interesting candidate, if any.
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize>;
+}
+
+impl<F: FnMut(&syn::File) -> bool> Oracle for F {
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        candidates.iter().position(self)
+    }
+}
+
+impl Oracle for Box<dyn Oracle + '_> {
+    fn jobs(&self) -> usize {
+        (**self).jobs()
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        (**self).first_interesting(candidates)
+    }
+}
+
+/// Decides whether a file on disk is interesting.
+pub trait FileTest: Sync {
+    fn test(&self, path: &Path) -> bool;
+}
+
+impl<T: FileTest + ?Sized> FileTest for Box<T> {
+    fn test(&self, path: &Path) -> bool {
+        (**self).test(path)
+    }
+}
+
+/// Writes each candidate to a temporary file, and tests the files
+/// concurrently.
+pub struct FileOracle<T> {
+    test: T,
+    jobs: usize,
+    source: Option<OriginalSource>,
+}
+
+impl<T: FileTest> FileOracle<T> {
+    pub fn new(test: T, jobs: usize) -> Self {
+        FileOracle { test, jobs, source: None }
+    }
+
+    /// Write candidates using the original source text, instead of printing
+    /// their tokens.
+    pub fn keep_formatting(mut self, source: OriginalSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn test_path<P: AsRef<Path>>(&self, path: &P) -> bool {
+        self.test.test(path.as_ref())
+    }
+
+    fn write_file(&self, contents: &syn::File) -> NamedTempFile {
+        let mut file = tempfile::Builder::new().prefix("test").tempfile().unwrap();
+        match &self.source {
+            Some(source) => file.write_all(source.print(contents).as_bytes()).unwrap(),
+            None => write!(file, "{}", contents.into_token_stream()).unwrap(),
+        }
+        file.flush().unwrap();
+        file
+    }
+}
+
+impl<T: FileTest> Oracle for FileOracle<T> {
+    fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    fn first_interesting(&mut self, candidates: &[syn::File]) -> Option<usize> {
+        let files = candidates.iter().map(|candidate| self.write_file(candidate)).collect::<Vec<_>>();
+        let paths = files.iter().map(|file| Some(file.path())).collect::<Vec<_>>();
+        first_interesting_path(&self.test, &paths)
+    }
+}
+
+/// Tests `paths` concurrently, and returns the index of the first interesting
+/// one. A `None` path couldn't be written and isn't interesting.
+///
+/// The candidates must all be written before testing, since syn types can't
+/// be sent to other threads.
+pub(crate) fn first_interesting_path<T: FileTest + ?Sized>(test: &T, paths: &[Option<&Path>]) -> Option<usize> {
+    if let [path] = paths {
+        return if path.is_some_and(|path| test.test(path)) { Some(0) } else { None };
+    }
+
+    thread::scope(|s| {
+        paths.iter()
+            .map(|&path| s.spawn(move || path.is_some_and(|path| test.test(path))))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .position(|handle| handle.join().unwrap())
+    })
+}
+
+/// Runs a command on each candidate, the candidate is interesting if the
+/// command succeeds.
+pub struct CommandTest {
+    cmd: Vec<OsString>,
+    timeout: Option<Timeout>,
+    in_dir: bool,
+}
+
+/// How long a command may run, and whether it's interesting if it doesn't
+/// finish in time.
+#[derive(Copy, Clone)]
+pub struct Timeout {
+    pub duration: Duration,
+    pub interesting: bool,
+}
+
+impl CommandTest {
+    pub fn new(cmd: Vec<OsString>, timeout: Option<Timeout>) -> Self {
+        CommandTest { cmd, timeout, in_dir: false }
+    }
+
+    /// Run the command in the directory that's tested, instead of passing
+    /// the path of the file as the last argument.
+    pub fn in_dir(mut self) -> Self {
+        self.in_dir = true;
+        self
+    }
+}
+
+impl FileTest for CommandTest {
+    fn test(&self, path: &Path) -> bool {
+        let (cmd, args) = self.cmd.split_first().expect("validated");
+        let mut command = Command::new(cmd);
+        command.args(args);
+        if self.in_dir {
+            command.current_dir(path);
+        } else {
+            command.arg(path);
+        }
+        command.stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        match run_command(&mut command, self.timeout.map(|t| t.duration)) {
+            Ok(Some(stat)) => stat.success(),
+            Ok(None) => self.timeout.expect("only times out with a timeout").interesting,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Runs `command` to completion, or until `timeout` has passed. Returns
+/// `None` if the command timed out, and an error if it couldn't be started or
+/// waited for.
+pub(crate) fn run_command(command: &mut Command, timeout: Option<Duration>) -> io::Result<Option<ExitStatus>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return command.status().map(Some),
+    };
+
+    // run the command in its own process group, so that anything it
+    // spawns can be killed along with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+
+    let mut child = command.spawn()?;
+    let stat = wait_timeout(&mut child, timeout);
+    // make sure the command is gone if it timed out, or if waiting for it
+    // failed
+    if !matches!(stat, Ok(Some(_))) {
+        kill_process_group(&mut child);
+    }
+    stat
+}
+
+fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    let mut poll_interval = Duration::from_millis(1);
+    loop {
+        if let Some(stat) = child.try_wait()? {
+            return Ok(Some(stat));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(poll_interval.min(deadline - now));
+        poll_interval = (poll_interval * 2).min(Duration::from_millis(50));
+    }
+}
+
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    let _ = child.wait();
+}
@@ -15,39 +15,96 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{ffi::OsStr, fs, io::Write, path::PathBuf, process::{Command, Stdio}, sync::mpsc, thread::{self, JoinHandle}};
+use std::{collections::HashSet, ffi::OsStr, fs, io::Write, path::{Path, PathBuf}, process::{Command, Stdio}, sync::mpsc, thread::{self, JoinHandle}};
 
 use quote::ToTokens;
+use rust_reduce::source::OriginalSource;
 
 /// A type that will wait during `Drop` for all output operations to complete.
 pub struct WaitGuard {
-    path: Option<PathBuf>,
-    constructor: fn(PathBuf, bool) -> Box<OutputType>,
-    inner: Option<Box<OutputType>>,
+    path: PathBuf,
+    constructor: fn(bool, bool) -> Box<dyn OutputType>,
+    inner: Option<Box<dyn OutputType>>,
     need_backup: bool,
+    source: Option<OriginalSource>,
+    keep_formatting: bool,
+    module_tree: bool,
 }
 
 impl WaitGuard {
     pub fn new<T: OutputType, P: Into<PathBuf>>(path: P, need_backup: bool) -> Self {
         WaitGuard {
-            path: Some(path.into()),
+            path: path.into(),
             constructor: T::new,
             inner: None,
             need_backup,
+            source: None,
+            keep_formatting: false,
+            module_tree: false,
         }
     }
 
+    /// Output the original source text of the remaining code, instead of
+    /// formatting the tokens with `rustfmt`.
+    pub fn keep_formatting(&mut self, source: OriginalSource) {
+        self.source = Some(source);
+        self.keep_formatting = true;
+    }
+
+    /// Output one file per module that was inlined, next to the output file
+    /// like the original files are next to the root file. Files of modules
+    /// that were removed are deleted.
+    pub fn module_tree(&mut self, source: OriginalSource) {
+        self.source = Some(source);
+        self.module_tree = true;
+    }
+
     pub fn output_formatted(&mut self, reduced_file: &syn::File) {
-        let path = self.path.take();
-        let WaitGuard { constructor, need_backup, .. } = *self;
-        self.inner.get_or_insert_with(|| constructor(path.unwrap(), need_backup))
-            .output(reduced_file.into_token_stream().to_string())
+        let output = match &self.source {
+            Some(source) if self.module_tree => {
+                let files = source.module_files(reduced_file).into_iter()
+                    .map(|(path, file)| (self.output_path(source, path), self.print(&file)))
+                    .collect::<Vec<_>>();
+                let removed = source.paths()
+                    .map(|path| self.output_path(source, path))
+                    .filter(|path| !files.iter().any(|(file, _)| file == path))
+                    .collect();
+                Output { files, removed }
+            },
+            _ => Output { files: vec![(self.path.clone(), self.print(reduced_file))], removed: vec![] },
+        };
+        let WaitGuard { constructor, need_backup, keep_formatting, .. } = *self;
+        self.inner.get_or_insert_with(|| constructor(need_backup, !keep_formatting))
+            .output(output)
+    }
+
+    fn print(&self, file: &syn::File) -> String {
+        match &self.source {
+            Some(source) if self.keep_formatting => source.print(file),
+            _ => file.into_token_stream().to_string(),
+        }
+    }
+
+    /// Where to write the original file at `path`.
+    fn output_path(&self, source: &OriginalSource, path: &Path) -> PathBuf {
+        if path == source.root() {
+            return self.path.clone();
+        }
+        let relative = source.relative_path(path).expect("checked before reducing");
+        self.path.parent().unwrap_or_else(|| Path::new("")).join(relative)
     }
 }
 
+/// The files to write and the files to delete.
+pub struct Output {
+    files: Vec<(PathBuf, String)>,
+    removed: Vec<PathBuf>,
+}
+
 pub trait OutputType {
-    fn new(path: PathBuf, need_backup: bool) -> Box<OutputType> where Self: Sized;
-    fn output(&mut self, reduced_file: String);
+    #[allow(clippy::new_ret_no_self)]
+    fn new(need_backup: bool, rustfmt: bool) -> Box<dyn OutputType> where Self: Sized;
+    fn output(&mut self, output: Output);
 }
 
 pub struct AsyncWriter {
@@ -56,13 +113,14 @@ pub struct AsyncWriter {
 }
 
 impl OutputType for AsyncWriter {
-    fn new(file: PathBuf, need_backup: bool) -> Box<OutputType> {
+    fn new(need_backup: bool, rustfmt: bool) -> Box<dyn OutputType> {
         let (send, recv) = mpsc::channel();
 
         let thread = thread::spawn(move || {
             TargetFileWorker {
-                backed_up: !need_backup,
-                file,
+                need_backup,
+                backed_up: HashSet::new(),
+                rustfmt,
                 chan: recv,
             }.run()
         });
@@ -73,8 +131,8 @@ impl OutputType for AsyncWriter {
         })
     }
 
-    fn output(&mut self, reduced_file: String) {
-        self.chan.send(Message::Work(reduced_file)).unwrap()
+    fn output(&mut self, output: Output) {
+        self.chan.send(Message::Work(output)).unwrap()
     }
 }
 
@@ -87,79 +145,104 @@ impl Drop for AsyncWriter {
 
 pub struct LastWriter {
     worker: TargetFileWorker,
-    last: Option<String>,
+    last: Option<Output>,
 }
 
 impl OutputType for LastWriter {
-    fn new(file: PathBuf, need_backup: bool) -> Box<OutputType> {
+    fn new(need_backup: bool, rustfmt: bool) -> Box<dyn OutputType> {
         Box::new(LastWriter {
             worker: TargetFileWorker {
-                backed_up: !need_backup,
-                file,
+                need_backup,
+                backed_up: HashSet::new(),
+                rustfmt,
                 chan: mpsc::channel().1,
             },
             last: None,
         })
     }
 
-    fn output(&mut self, reduced_file: String) {
-        self.last = Some(reduced_file);
+    fn output(&mut self, output: Output) {
+        self.last = Some(output);
     }
 }
 
 impl Drop for LastWriter {
     fn drop(&mut self) {
         // unwrap ok: `new` is always immediately followed by `output`
-        self.worker.emit_formatted_file(self.last.take().unwrap());
+        self.worker.emit_output(self.last.take().unwrap());
     }
 }
 
 enum Message {
-    Work(String),
+    Work(Output),
     Quit
 }
 
 struct TargetFileWorker {
-    backed_up: bool,
-    file: PathBuf,
+    need_backup: bool,
+    backed_up: HashSet<PathBuf>,
+    rustfmt: bool,
     chan: mpsc::Receiver<Message>,
 }
 
 impl TargetFileWorker {
-    fn backup(&mut self) {
-        if !self.backed_up {
-            let mut orig = self.file.clone().into_os_string();
+    fn backup(&mut self, file: &Path) {
+        if self.need_backup && self.backed_up.insert(file.to_owned()) {
+            let mut orig = file.to_owned().into_os_string();
             orig.push(".orig");
-            if let Err(e) = fs::copy(&self.file, orig) {
+            if let Err(e) = fs::copy(file, orig) {
                 eprintln!("Failed to backup input file: {}", e);
                 std::process::exit(1);
             }
-            self.backed_up = true;
         }
     }
 
-    fn emit_formatted_file(&mut self, reduced_file: String) {
-        self.backup();
+    fn emit_output(&mut self, output: Output) {
+        eprintln!("{} bytes...", output.files.iter().map(|(_, text)| text.len()).sum::<usize>());
 
-        eprintln!("{} bytes...", reduced_file.len());
+        for (file, text) in output.files {
+            // module files may be in new directories
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir).unwrap();
+            }
+            self.backup(&file);
+            self.emit_formatted_file(&file, text);
+        }
+        for file in output.removed {
+            if !file.exists() {
+                continue;
+            }
+            self.backup(&file);
+            if let Err(e) = fs::remove_file(&file) {
+                eprintln!("Failed to remove {}: {}", file.display(), e);
+            }
+        }
+    }
 
-        match Command::new("rustfmt")
-            .stdout(if self.file == OsStr::new("-") {
-                    Stdio::inherit()
-                } else {
-                    Stdio::from(fs::File::create(&self.file).unwrap())
-                })
-            .stdin(Stdio::piped())
-            .spawn() {
-            Ok(mut child) => {
+    fn emit_formatted_file(&mut self, file: &Path, reduced_file: String) {
+        let rustfmt = if self.rustfmt {
+            Command::new("rustfmt")
+                .stdout(if file == OsStr::new("-") {
+                        Stdio::inherit()
+                    } else {
+                        Stdio::from(fs::File::create(file).unwrap())
+                    })
+                .stdin(Stdio::piped())
+                .spawn()
+                .ok()
+        } else {
+            None
+        };
+        match rustfmt {
+            Some(mut child) => {
                 child.stdin.take().unwrap().write_all(reduced_file.as_bytes()).unwrap();
                 child.wait().unwrap();
             }
-            Err(_) => {
-                if self.file == OsStr::new("-") {
-                    Box::new(std::io::stdout()) as Box<Write>
+            None => {
+                if file == OsStr::new("-") {
+                    Box::new(std::io::stdout()) as Box<dyn Write>
                 } else {
-                    Box::new(fs::File::create(&self.file).unwrap())
+                    Box::new(fs::File::create(file).unwrap())
                 }.write_all(reduced_file.as_bytes()).unwrap();
             }
         }
@@ -167,18 +250,18 @@ impl TargetFileWorker {
 
     fn run(&mut self) {
         let mut next = None;
-        while let Message::Work(mut tokens) = next.unwrap_or_else(|| self.chan.recv().unwrap()) {
+        while let Message::Work(mut output) = next.unwrap_or_else(|| self.chan.recv().unwrap()) {
             next = None;
 
             // skip ahead if there's more work in the queue
             while let Ok(msg) = self.chan.try_recv() {
                 match msg {
-                    Message::Work(new_toks) => tokens = new_toks,
+                    Message::Work(new_output) => output = new_output,
                     other => next = Some(other),
                 }
             }
 
-            self.emit_formatted_file(tokens)
+            self.emit_output(output)
         }
     }
 }
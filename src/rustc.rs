@@ -0,0 +1,339 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Built-in tests that compile the candidate with `rustc`.
+
+use std::{ffi::OsString, fs, io::{Read, Seek, SeekFrom}, path::Path, process::{Command, Output, Stdio}, time::Duration};
+
+use regex::Regex;
+
+use crate::oracle::{self, FileTest, Timeout};
+
+/// What makes a `rustc` run interesting.
+pub enum Matcher {
+    /// Compilation fails with this error code, e.g. `E0277`.
+    ErrorCode(String),
+    /// The compiler output matches this regular expression.
+    StderrRegex(Regex),
+    /// The compiler crashes with an internal compiler error.
+    Ice,
+    /// The compiler crashes with an internal compiler error with this
+    /// signature.
+    IceSignature(IceSignature),
+}
+
+/// Identifies an internal compiler error by the panic message and the top
+/// frames of the query stack.
+///
+/// Paths of the input file, `DefId` indices and the name of the local crate
+/// are normalized, since those change while reducing.
+#[derive(PartialEq, Eq, Debug)]
+pub struct IceSignature {
+    message: Vec<String>,
+    frames: Vec<String>,
+}
+
+impl IceSignature {
+    /// Parse the signature from the output of a `rustc` run on `path`,
+    /// keeping at most `frames` query stack frames. Returns `None` if the
+    /// output doesn't contain an ICE.
+    pub fn parse(stderr: &str, path: &Path, frames: usize) -> Option<Self> {
+        // spans are printed as `path:line:col`, or as `path:line:col: line:col (#0)`
+        let path_re = Regex::new(&format!(r"{}(:\d+:\d+(: \d+:\d+)?( \(#\d+\))?)?", regex::escape(&path.to_string_lossy()))).unwrap();
+        // the name of the local crate (0) is derived from the file name
+        let def_id_re = Regex::new(r"DefId\((\d+):\d+( ~ [\w-]+\[[0-9a-f]+\])?").unwrap();
+        let normalize = |line: &str| {
+            let line = path_re.replace_all(line.trim(), "<input>");
+            def_id_re.replace_all(&line, |caps: &regex::Captures| match caps.get(2) {
+                Some(_) if &caps[1] == "0" => String::from("DefId(_ ~ <input>"),
+                Some(krate) => format!("DefId(_{}", krate.as_str()),
+                None => String::from("DefId(_"),
+            }).into_owned()
+        };
+
+        let mut message = vec![];
+        let mut lines = stderr.lines();
+        while let Some(line) = lines.next() {
+            if line.starts_with("error: internal compiler error:") {
+                message.push(normalize(line));
+            } else if line.starts_with("thread '") && line.contains("' panicked at ") {
+                message.push(normalize(line));
+                // since Rust 1.73, the message is on the next line
+                if line.ends_with(':') {
+                    message.extend(lines.next().map(normalize));
+                }
+            } else if line == "query stack during panic:" {
+                break;
+            }
+        }
+        if message.is_empty() {
+            return None;
+        }
+
+        let frames = lines
+            .take_while(|line| *line != "end of query stack")
+            .filter(|line| line.starts_with('#'))
+            .take(frames)
+            .map(|line| normalize(line.split_once(' ').map_or("", |(_, frame)| frame)))
+            .collect();
+
+        Some(IceSignature { message, frames })
+    }
+}
+
+/// How to invoke `rustc`.
+#[derive(Clone)]
+pub struct Rustc {
+    rustc: OsString,
+    flags: Vec<OsString>,
+    edition: Option<String>,
+}
+
+impl Default for Rustc {
+    fn default() -> Self {
+        Rustc {
+            rustc: "rustc".into(),
+            flags: vec![],
+            edition: None,
+        }
+    }
+}
+
+impl Rustc {
+    /// The `rustc` binary to run (default `rustc`).
+    pub fn rustc<S: Into<OsString>>(mut self, rustc: S) -> Self {
+        self.rustc = rustc.into();
+        self
+    }
+
+    /// Extra flags to pass to `rustc`, e.g. `--crate-type lib`.
+    pub fn flags<I: IntoIterator<Item = S>, S: Into<OsString>>(mut self, flags: I) -> Self {
+        self.flags = flags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The edition to compile with (default is `rustc`'s default).
+    pub fn edition<S: Into<String>>(mut self, edition: S) -> Self {
+        self.edition = Some(edition.into());
+        self
+    }
+
+    /// `config` is passed before the other flags, so that it may start with
+    /// a `+toolchain` override.
+    fn command(&self, config: &[OsString]) -> Command {
+        let mut command = Command::new(&self.rustc);
+        command.args(config).args(&self.flags);
+        if let Some(edition) = &self.edition {
+            command.arg("--edition").arg(edition);
+        }
+        // don't write ICE reports to the working directory
+        command.env("RUSTC_ICE", "0");
+        command
+    }
+
+    fn describe(&self) -> String {
+        let mut desc = self.rustc.to_string_lossy().into_owned();
+        for flag in &self.flags {
+            desc.push(' ');
+            desc.push_str(&flag.to_string_lossy());
+        }
+        if let Some(edition) = &self.edition {
+            desc.push_str(" --edition ");
+            desc.push_str(edition);
+        }
+        desc
+    }
+}
+
+/// Compiles each candidate with `rustc`, the candidate is interesting if the
+/// outcome matches.
+pub struct RustcTest {
+    rustc: Rustc,
+    matcher: Matcher,
+    timeout: Option<Timeout>,
+}
+
+impl RustcTest {
+    pub fn new(rustc: Rustc, matcher: Matcher) -> Self {
+        RustcTest { rustc, matcher, timeout: None }
+    }
+
+    pub fn timeout(mut self, timeout: Timeout) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// A description of the compiler invocation and what is interesting,
+    /// suitable to identify the outcomes in a cache file.
+    pub fn describe(&self) -> String {
+        let mut desc = self.rustc.describe();
+        match &self.matcher {
+            Matcher::ErrorCode(code) => desc.push_str(&format!(": error {}", code)),
+            Matcher::StderrRegex(re) => desc.push_str(&format!(": stderr matches {}", re)),
+            Matcher::Ice => desc.push_str(": ICE"),
+            Matcher::IceSignature(sig) => {
+                desc.push_str(": ICE ");
+                desc.push_str(&sig.message.join(" "));
+                for frame in &sig.frames {
+                    desc.push_str(" / ");
+                    desc.push_str(frame);
+                }
+            },
+        }
+        desc
+    }
+
+    /// Only accept ICEs with the same signature as the ICE that occurs when
+    /// compiling `path`, comparing at most `frames` query stack frames.
+    /// Returns `false` if compiling `path` doesn't result in an ICE.
+    pub fn require_ice_signature(&mut self, path: &Path, frames: usize) -> bool {
+        match self.compile(path) {
+            Some(Outcome::Exited(output)) => match IceSignature::parse(&String::from_utf8_lossy(&output.stderr), path, frames) {
+                Some(sig) => {
+                    self.matcher = Matcher::IceSignature(sig);
+                    true
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn compile(&self, path: &Path) -> Option<Outcome> {
+        let out_dir = tempfile::tempdir().ok()?;
+        let mut command = self.rustc.command(&[]);
+        command.arg("--out-dir").arg(out_dir.path()).arg(path);
+        run_captured(&mut command, self.timeout.map(|t| t.duration))
+    }
+}
+
+impl FileTest for RustcTest {
+    fn test(&self, path: &Path) -> bool {
+        let output = match self.compile(path) {
+            Some(Outcome::Exited(output)) => output,
+            Some(Outcome::TimedOut) => return self.timeout.expect("only times out with a timeout").interesting,
+            None => return false,
+        };
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        match &self.matcher {
+            Matcher::ErrorCode(code) => stderr.contains(&format!("error[{}]", code)),
+            Matcher::StderrRegex(re) => re.is_match(&stderr),
+            // rustc exits with 101 if it panics
+            Matcher::Ice => output.status.code() == Some(101),
+            Matcher::IceSignature(sig) => {
+                output.status.code() == Some(101) && IceSignature::parse(&stderr, path, sig.frames.len()).as_ref() == Some(sig)
+            },
+        }
+    }
+}
+
+/// Compiles each candidate into a program with two different configurations
+/// and runs both programs, the candidate is interesting if the programs
+/// behave differently.
+///
+/// The behavior consists of the exit status and the standard output. The
+/// standard error is ignored, since panic messages contain thread IDs. The
+/// candidate is not interesting if it fails to compile, or if compiling or
+/// running takes longer than the timeout.
+pub struct DifferentialTest {
+    rustc: Rustc,
+    configs: [Vec<OsString>; 2],
+    timeout: Duration,
+}
+
+impl DifferentialTest {
+    /// `a` and `b` are the flags that differ between the configurations, e.g.
+    /// `-C opt-level=0` and `-C opt-level=3`, or `+stable` and `+nightly`.
+    pub fn new<I: IntoIterator<Item = S>, S: Into<OsString>>(rustc: Rustc, a: I, b: I, timeout: Duration) -> Self {
+        let config = |flags: I| flags.into_iter().map(Into::into).collect();
+        DifferentialTest {
+            rustc,
+            configs: [config(a), config(b)],
+            timeout,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let config = |flags: &[OsString]| flags.iter().map(|flag| flag.to_string_lossy()).collect::<Vec<_>>().join(" ");
+        format!("{}: [{}] differs from [{}]", self.rustc.describe(), config(&self.configs[0]), config(&self.configs[1]))
+    }
+
+    fn compile_and_run(&self, path: &Path, config: &[OsString], out_dir: &Path, name: &str) -> Option<Output> {
+        let program = out_dir.join(name);
+        let mut command = self.rustc.command(config);
+        command.arg("-o").arg(&program).arg(path);
+        match run_captured(&mut command, Some(self.timeout))? {
+            Outcome::Exited(output) if output.status.success() => {},
+            _ => return None,
+        }
+
+        match run_captured(&mut Command::new(&program), Some(self.timeout))? {
+            Outcome::Exited(output) => Some(output),
+            Outcome::TimedOut => None,
+        }
+    }
+}
+
+impl FileTest for DifferentialTest {
+    fn test(&self, path: &Path) -> bool {
+        let out_dir = match tempfile::tempdir() {
+            Ok(out_dir) => out_dir,
+            Err(_) => return false,
+        };
+        let a = match self.compile_and_run(path, &self.configs[0], out_dir.path(), "a") {
+            Some(a) => a,
+            None => return false,
+        };
+        let b = match self.compile_and_run(path, &self.configs[1], out_dir.path(), "b") {
+            Some(b) => b,
+            None => return false,
+        };
+        a.status != b.status || a.stdout != b.stdout
+    }
+}
+
+enum Outcome {
+    Exited(Output),
+    TimedOut,
+}
+
+/// Runs `command` like [`oracle::run_command`], capturing its output.
+fn run_captured(command: &mut Command, timeout: Option<Duration>) -> Option<Outcome> {
+    let stdout = tempfile::tempfile().ok()?;
+    let stderr = tempfile::tempfile().ok()?;
+    command.stdin(Stdio::null())
+        .stdout(stdout.try_clone().ok()?)
+        .stderr(stderr.try_clone().ok()?);
+
+    match oracle::run_command(command, timeout) {
+        Ok(Some(status)) => Some(Outcome::Exited(Output {
+            status,
+            stdout: read_to_end(stdout)?,
+            stderr: read_to_end(stderr)?,
+        })),
+        Ok(None) => Some(Outcome::TimedOut),
+        Err(_) => None,
+    }
+}
+
+fn read_to_end(mut file: fs::File) -> Option<Vec<u8>> {
+    let mut buf = vec![];
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_end(&mut buf).ok()?;
+    Some(buf)
+}
@@ -0,0 +1,440 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Print reduced files using the original source text, so that the code
+//! that's left keeps its formatting and comments.
+//!
+//! Every token that wasn't created by a pass has a span that points into the
+//! original source. If two tokens were next to each other in the original
+//! source, the original text between them is copied as well. Otherwise, the
+//! comments and line breaks surrounding the removed code are kept, so that the
+//! remaining code stays on its own lines.
+
+use std::{collections::HashMap, fs, io, ops::Range, path::{Component, Path, PathBuf}};
+
+use proc_macro2::{Delimiter, Spacing, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::visit_mut::{self, VisitMut};
+
+/// The original source files of an inlined file.
+#[derive(Clone)]
+pub struct OriginalSource {
+    root: PathBuf,
+    /// The source files, by the name `proc_macro2` gives them.
+    files: HashMap<String, SourceFile>,
+    /// The file name of each inlined module, by the location of its name.
+    modules: HashMap<(String, usize), String>,
+}
+
+#[derive(Clone)]
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+    /// Where the part of `text` that `syn` parses starts.
+    offset: usize,
+    /// The byte range of each token, in order.
+    tokens: Vec<Range<usize>>,
+    /// The index in `tokens` of each token, by its start.
+    ordinals: HashMap<usize, usize>,
+}
+
+impl SourceFile {
+    fn read(path: PathBuf) -> io::Result<Self> {
+        let text = fs::read_to_string(&path)?;
+        let offset = content_start(&text);
+        let stream = text[offset..].parse::<TokenStream>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+
+        let mut tokens: Vec<Range<usize>> = vec![];
+        for token in leaves(stream) {
+            let range = token.span.byte_range();
+            let range = (range.start + offset)..(range.end + offset);
+            // see `OriginalSource::print`
+            if tokens.last().is_some_and(|prev| covers(prev, &range)) {
+                continue;
+            }
+            tokens.push(range);
+        }
+        let ordinals = tokens.iter().enumerate().map(|(ordinal, range)| (range.start, ordinal)).collect();
+        Ok(SourceFile { path, text, offset, tokens, ordinals })
+    }
+
+    /// The text between the previous token and this one.
+    fn text_before(&self, ordinal: usize) -> &str {
+        let start = ordinal.checked_sub(1).map_or(0, |prev| self.tokens[prev].end);
+        &self.text[start..self.tokens[ordinal].start]
+    }
+
+    /// The text between this token and the next one.
+    fn text_after(&self, ordinal: usize) -> &str {
+        let end = self.tokens.get(ordinal + 1).map_or(self.text.len(), |next| next.start);
+        &self.text[self.tokens[ordinal].end..end]
+    }
+
+    /// The line breaks, comments and indentation before this token if the
+    /// previous token was removed. That's everything for the first token of
+    /// the file.
+    fn comments_before(&self, ordinal: usize) -> &str {
+        let text = self.text_before(ordinal);
+        if ordinal == 0 {
+            return text;
+        }
+        match text.find('\n') {
+            Some(newline) => &text[newline..],
+            None => "",
+        }
+    }
+
+    /// The whitespace at the start of the line this token is on.
+    fn indentation(&self, ordinal: usize) -> &str {
+        let start = self.tokens[ordinal].start;
+        let line = self.text[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let text = &self.text[line..start];
+        &text[..text.len() - text.trim_start().len()]
+    }
+
+    /// The comments on the same line after this token if the next token was
+    /// removed. That's everything for the last token of the file.
+    fn comments_after(&self, ordinal: usize) -> &str {
+        let text = self.text_after(ordinal);
+        if ordinal + 1 == self.tokens.len() {
+            return text;
+        }
+        match text.find('\n') {
+            Some(newline) => text[..newline].trim_end(),
+            None => "",
+        }
+    }
+}
+
+impl OriginalSource {
+    /// `file` must be the result of inlining the modules of the file at
+    /// `root`. The source files are read again from disk.
+    pub fn new(root: &Path, file: &syn::File) -> io::Result<Self> {
+        let mut collector = FileCollector {
+            path: root.to_owned(),
+            root: true,
+            context: vec![],
+            paths: HashMap::new(),
+            modules: HashMap::new(),
+        };
+        if let Some(name) = first_file_name(file.into_token_stream()) {
+            collector.paths.insert(name, root.to_owned());
+        }
+        // only `visit-mut` is enabled in `syn`
+        collector.visit_file_mut(&mut file.clone());
+
+        let mut files = HashMap::new();
+        for (name, path) in collector.paths {
+            files.insert(name, SourceFile::read(path)?);
+        }
+        Ok(OriginalSource { root: root.to_owned(), files, modules: collector.modules })
+    }
+
+    /// The path of each original source file.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.values().map(|file| file.path.as_path())
+    }
+
+    /// The path of the file the modules were inlined into.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The path of the original file at `path` relative to the directory of
+    /// the root file, if it's in that directory.
+    pub fn relative_path<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        let root_dir = self.root.parent().unwrap_or_else(|| Path::new(""));
+        let relative = path.strip_prefix(root_dir).ok()?;
+        if relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            Some(relative)
+        } else {
+            None
+        }
+    }
+
+    /// Split `file`, which must be a reduced version of the file this was
+    /// created from, into the files of the original module tree. Each
+    /// inlined module that's left becomes a `mod foo;` declaration again.
+    /// Returns the contents of each file along with its original path, the
+    /// root file first.
+    pub fn module_files(&self, file: &syn::File) -> Vec<(&Path, syn::File)> {
+        let mut splitter = ModuleSplitter { source: self, files: vec![] };
+        let mut file = file.clone();
+        splitter.visit_file_mut(&mut file);
+        splitter.files.insert(0, (self.root.as_path(), file));
+        splitter.files
+    }
+
+    /// Print `file`, which must be a reduced version of the file this was
+    /// created from.
+    pub fn print(&self, file: &syn::File) -> String {
+        let mut out = String::new();
+        let mut prev: Option<Token> = None;
+        for token in leaves(file.into_token_stream()) {
+            let location = self.location(token.span);
+            // doc comments are a single token in the source, but several
+            // tokens after parsing
+            if let (Some(Token { location: Some((prev_name, prev_range, _)), .. }), Some((name, range))) = (&prev, &location) {
+                if prev_name == name && covers(prev_range, range) {
+                    continue;
+                }
+            }
+            let location = location.and_then(|(name, range)| {
+                let ordinal = *self.files[&name].ordinals.get(&range.start)?;
+                Some((name, range, ordinal))
+            });
+
+            match (&prev, &location) {
+                (Some(Token { location: Some((prev_name, _, prev_ordinal)), .. }), Some((name, _, ordinal)))
+                    if prev_name == name && *ordinal == prev_ordinal + 1 => {
+                    out.push_str(self.files[name].text_before(*ordinal));
+                },
+                _ => {
+                    let mut separator = String::new();
+                    if let Some(Token { location: Some((prev_name, _, prev_ordinal)), .. }) = &prev {
+                        separator.push_str(self.files[prev_name].comments_after(*prev_ordinal));
+                    }
+                    if let Some((name, _, ordinal)) = &location {
+                        let comments = self.files[name].comments_before(*ordinal);
+                        // the start of an inlined module
+                        if prev.is_some() && *ordinal == 0 && !comments.starts_with('\n') {
+                            separator.push('\n');
+                        }
+                        separator.push_str(comments);
+                    }
+                    // a trailing line comment would comment out the token
+                    if separator.rsplit('\n').next().is_some_and(|line| line.contains("//")) {
+                        separator.push('\n');
+                        if let Some((name, _, ordinal)) = &location {
+                            separator.push_str(self.files[name].indentation(*ordinal));
+                        }
+                    }
+                    if separator.is_empty() {
+                        if let Some(prev) = &prev {
+                            if needs_space(prev, &token) {
+                                separator.push(' ');
+                            }
+                        }
+                    }
+                    out.push_str(&separator);
+                },
+            }
+
+            match &location {
+                Some((name, range, _)) => out.push_str(&self.files[name].text[range.clone()]),
+                None => out.push_str(&token.text),
+            }
+            prev = Some(Token { location, ..token });
+        }
+
+        if let Some(Token { location: Some((name, _, ordinal)), .. }) = &prev {
+            out.push_str(self.files[name].comments_after(*ordinal));
+        }
+        let mut out = out.trim_start_matches('\n').to_owned();
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out
+    }
+
+    fn location(&self, span: Span) -> Option<(String, Range<usize>)> {
+        let name = span.file();
+        let offset = self.files.get(&name)?.offset;
+        let range = span.byte_range();
+        Some((name, (range.start + offset)..(range.end + offset)))
+    }
+}
+
+/// A token, or the opening or closing delimiter of a group.
+struct Token {
+    text: String,
+    span: Span,
+    joint: bool,
+    location: Option<(String, Range<usize>, usize)>,
+}
+
+fn leaves(tokens: TokenStream) -> Vec<Token> {
+    fn push(leaves: &mut Vec<Token>, text: String, span: Span, joint: bool) {
+        leaves.push(Token { text, span, joint, location: None });
+    }
+
+    let mut leaves = vec![];
+    for tree in tokens {
+        match tree {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                if !open.is_empty() {
+                    push(&mut leaves, open.to_owned(), group.span_open(), false);
+                }
+                leaves.extend(self::leaves(group.stream()));
+                if !close.is_empty() {
+                    push(&mut leaves, close.to_owned(), group.span_close(), false);
+                }
+            },
+            TokenTree::Ident(ident) => push(&mut leaves, ident.to_string(), ident.span(), false),
+            TokenTree::Punct(punct) => push(&mut leaves, punct.as_char().to_string(), punct.span(), punct.spacing() == Spacing::Joint),
+            TokenTree::Literal(literal) => push(&mut leaves, literal.to_string(), literal.span(), false),
+        }
+    }
+    leaves
+}
+
+/// Whether tokens that weren't next to each other in the original source
+/// need to be separated.
+fn needs_space(prev: &Token, next: &Token) -> bool {
+    let word = |c: char| c.is_alphanumeric() || c == '_' || c == '"' || c == '\'';
+    let (prev_char, next_char) = match (prev.text.chars().last(), next.text.chars().next()) {
+        (Some(prev), Some(next)) => (prev, next),
+        _ => return false,
+    };
+    if word(prev_char) && word(next_char) {
+        return true;
+    }
+    if prev.joint || matches!(prev_char, '(' | '[' | '.' | '!' | '#' | '&') {
+        return false;
+    }
+    !matches!(next_char, ')' | ']' | ',' | ';' | '.' | '!' | '(' | '?' | ':')
+}
+
+/// Whether `inner` is part of the token at `outer`.
+fn covers(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// The start of the text that `syn::parse_file` parses, after a byte order
+/// mark and a shebang line.
+fn content_start(text: &str) -> usize {
+    let start = if text.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+    let content = &text[start..];
+    if content.starts_with("#!") && !content[2..].trim_start().starts_with('[') {
+        start + content.find('\n').unwrap_or(content.len())
+    } else {
+        start
+    }
+}
+
+fn first_file_name(tokens: TokenStream) -> Option<String> {
+    leaves(tokens).into_iter()
+        .map(|token| token.span.file())
+        .find(|name| name != "<unspecified>")
+}
+
+/// Finds the files of the modules that were inlined, using the same rules as
+/// `syn_inline_mod`.
+struct FileCollector {
+    path: PathBuf,
+    root: bool,
+    /// The modules and `#[path]` attributes since the start of the current
+    /// file.
+    context: Vec<(PathBuf, bool)>,
+    paths: HashMap<String, PathBuf>,
+    modules: HashMap<(String, usize), String>,
+}
+
+impl FileCollector {
+    fn candidates(&self) -> Vec<PathBuf> {
+        let mut dir = self.path.parent().map(ToOwned::to_owned).unwrap_or_default();
+        if !self.root && self.path.file_name().is_none_or(|name| name != "mod.rs") {
+            dir.push(self.path.file_stem().unwrap_or_default());
+        }
+        for (segment, _) in &self.context {
+            dir.push(segment);
+        }
+        match self.context.last() {
+            Some((_, true)) => vec![dir.with_extension("rs"), dir.join("mod.rs")],
+            _ => vec![dir],
+        }
+    }
+}
+
+impl VisitMut for FileCollector {
+    fn visit_item_mod_mut(&mut self, i: &mut syn::ItemMod) {
+        let path_attr = i.attrs.iter().find_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue { path, lit: syn::Lit::Str(lit), .. })) if path.is_ident("path") => Some(lit.value()),
+            _ => None,
+        });
+        self.context.push(match path_attr {
+            Some(path) => (path.into(), false),
+            None => (i.ident.to_string().into(), true),
+        });
+
+        let inlined = match &i.content {
+            // the braces of an inlined module don't have a location
+            Some((brace, items)) if brace.span.file() == "<unspecified>" && !items.is_empty() => {
+                let tokens = items.iter().map(ToTokens::into_token_stream).collect();
+                let path = self.candidates().into_iter().find(|path| path.exists());
+                first_file_name(tokens).zip(path)
+            },
+            _ => None,
+        };
+
+        match inlined {
+            Some((name, path)) => {
+                self.paths.insert(name.clone(), path.clone());
+                self.modules.insert(name_location(&i.ident), name);
+                let parent = std::mem::replace(self, FileCollector {
+                    path,
+                    root: false,
+                    context: vec![],
+                    paths: HashMap::new(),
+                    modules: HashMap::new(),
+                });
+                visit_mut::visit_item_mod_mut(self, i);
+                let child = std::mem::replace(self, parent);
+                self.paths.extend(child.paths);
+                self.modules.extend(child.modules);
+            },
+            None => visit_mut::visit_item_mod_mut(self, i),
+        }
+
+        self.context.pop();
+    }
+}
+
+/// Moves the contents of inlined modules back to their own files.
+struct ModuleSplitter<'a> {
+    source: &'a OriginalSource,
+    files: Vec<(&'a Path, syn::File)>,
+}
+
+impl VisitMut for ModuleSplitter<'_> {
+    fn visit_item_mod_mut(&mut self, i: &mut syn::ItemMod) {
+        // split nested modules first, so they're declarations in this file
+        visit_mut::visit_item_mod_mut(self, i);
+
+        let name = match self.source.modules.get(&name_location(&i.ident)) {
+            Some(name) if i.content.is_some() => name,
+            _ => return,
+        };
+        let (_, items) = i.content.take().expect("checked above");
+        let (attrs, inner) = i.attrs.drain(..).partition(|attr| matches!(attr.style, syn::AttrStyle::Outer));
+        i.attrs = attrs;
+        i.semi = Some(Default::default());
+        self.files.push((&self.source.files[name].path, syn::File { shebang: None, attrs: inner, items }));
+    }
+}
+
+/// Identifies a module by the location of its name in the parent file.
+fn name_location(ident: &syn::Ident) -> (String, usize) {
+    (ident.span().file(), ident.span().byte_range().start)
+}
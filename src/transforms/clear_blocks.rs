@@ -15,61 +15,73 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
 
-/// Try to replace each block with `{ unimplemented!() }`, similar to `rustc`'s
-/// every body loops printer.
-
-use std::mem;
+//! Try to replace each block with `{ unimplemented!() }`, similar to `rustc`'s
+//! every body loops printer.
 
 use syn::visit_mut::*;
 use quote::quote;
 
-pub fn clear_blocks<F: FnMut(&syn::File) -> bool>(file: &mut syn::File, mut try_compile: F) {
-    let mut visitor = BlockVisitor {
-        backup: None,
-        cur_index: 0,
-        target_index: 1,
-        unimplemented: syn::parse2(quote!( { unimplemented!() } )).unwrap()
-    };
+use crate::oracle::Oracle;
+use super::Transform;
 
-    loop {
-        visitor.cur_index = 0;
-        visit_file_mut(&mut visitor, file);
+pub struct ClearBlocks;
 
-        // no more changes to be made
-        if visitor.backup.is_none() {
-            break
-        }
+impl Transform for ClearBlocks {
+	fn name(&self) -> &'static str {
+		"clear-blocks"
+	}
+
+	fn description(&self) -> &'static str {
+		"Replace blocks with `{ unimplemented!() }`"
+	}
+
+	fn run(&self, file: &mut syn::File, oracle: &mut dyn Oracle) {
+		clear_blocks(file, oracle)
+	}
+}
 
-        if try_compile(file) {
-            // this change works, keep it!
-            visitor.backup = None;
+pub fn clear_blocks<O: Oracle + ?Sized>(file: &mut syn::File, oracle: &mut O) {
+    let unimplemented = syn::parse2(quote!( { unimplemented!() } )).unwrap();
+
+    super::search(file, oracle, |file, index| {
+        let mut visitor = BlockVisitor {
+            changed: false,
+            cur_index: 0,
+            target_index: index,
+            unimplemented: &unimplemented,
+        };
+        visit_file_mut(&mut visitor, file);
+        if visitor.changed {
+            Some((visitor.target_index, visitor.target_index))
+        } else {
+            None
         }
-    }
+    });
 }
 
-struct BlockVisitor {
-	backup: Option<syn::Block>,
+struct BlockVisitor<'a> {
+	changed: bool,
 	cur_index: usize,
 	target_index: usize,
-	unimplemented: syn::Block,
+	unimplemented: &'a syn::Block,
 }
 
-impl VisitMut for BlockVisitor {
+impl VisitMut for BlockVisitor<'_> {
 	fn visit_block_mut(&mut self, i: &mut syn::Block) {
-		self.cur_index += 1;
+		if self.changed {
+			return;
+		}
 
 		if self.target_index == self.cur_index {
-			if let Some(backup) = self.backup.take() {
-				// the change we tried didn't work. revert and try the next
-				// possible change
-				mem::replace(i, backup);
-			} else if *i != self.unimplemented {
-				self.backup = Some(mem::replace(i, self.unimplemented.clone()));
+			if !i.stmts.is_empty() && i != self.unimplemented {
+				*i = self.unimplemented.clone();
+				self.changed = true;
 				return;
 			}
 
 			self.target_index += 1;
 		}
+		self.cur_index += 1;
 
 		visit_block_mut(self, i)
 	}
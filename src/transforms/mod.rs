@@ -18,3 +18,103 @@
 pub mod prune_items;
 pub mod remove_doc_attrs;
 pub mod clear_blocks;
+pub mod remove_stmts;
+pub mod remove_arms;
+pub mod simplify_exprs;
+pub mod remove_params;
+pub mod remove_fields;
+pub mod remove_variants;
+pub mod prune_generics;
+
+use syn::punctuated::Punctuated;
+
+use crate::oracle::Oracle;
+
+/// A reduction pass.
+pub trait Transform {
+    /// The name used to select this pass.
+    fn name(&self) -> &'static str;
+
+    /// A one-line description of this pass.
+    fn description(&self) -> &'static str;
+
+    /// Try to reduce `file`, keeping only changes that `oracle` finds
+    /// interesting.
+    fn run(&self, file: &mut syn::File, oracle: &mut dyn Oracle);
+}
+
+/// All passes, in the order they run by default.
+pub const ALL: &[&dyn Transform] = &[
+    &prune_items::PruneItems,
+    &remove_doc_attrs::RemoveDocAttrs,
+    &clear_blocks::ClearBlocks,
+    &remove_stmts::RemoveStmts,
+    &remove_arms::RemoveArms,
+    &simplify_exprs::SimplifyExprs,
+    &remove_params::RemoveParams,
+    &remove_fields::RemoveFields,
+    &remove_variants::RemoveVariants,
+    &prune_generics::PruneGenerics,
+];
+
+/// Find a pass by its name.
+pub fn by_name(name: &str) -> Option<&'static dyn Transform> {
+    ALL.iter().copied().find(|pass| pass.name() == name)
+}
+
+/// Try changes to `file` in order, keeping those that the oracle finds
+/// interesting.
+///
+/// `apply` should make the change numbered `index` to the file it's passed,
+/// or if that change can't be made, the first possible change after it. It
+/// returns the number of the change that was made, and the number of the
+/// change to try next if this change is kept, or `None` if there are no more
+/// changes to be made. Up to `oracle.jobs()` changes are tested at a time,
+/// the first interesting one is kept.
+///
+/// Returns whether any change was kept.
+pub fn search<O, F>(file: &mut syn::File, oracle: &mut O, mut apply: F) -> bool
+    where O: Oracle + ?Sized, F: FnMut(&mut syn::File, usize) -> Option<(usize, usize)>
+{
+    let mut index = 0;
+    let mut changed = false;
+    loop {
+        let mut candidates = vec![];
+        let mut next_indices = vec![];
+        let mut next = index;
+        while candidates.len() < oracle.jobs() {
+            let mut candidate = file.clone();
+            match apply(&mut candidate, next) {
+                Some((applied, next_if_kept)) => {
+                    candidates.push(candidate);
+                    next_indices.push(next_if_kept);
+                    next = applied + 1;
+                },
+                None => break,
+            }
+        }
+
+        // no more changes to be made
+        if candidates.is_empty() {
+            return changed;
+        }
+
+        match oracle.first_interesting(&candidates) {
+            Some(i) => {
+                // this change works, keep it!
+                *file = candidates.swap_remove(i);
+                index = next_indices[i];
+                changed = true;
+            },
+            None => index = next,
+        }
+    }
+}
+
+/// Remove the element at `index` from `list`.
+fn remove_at<T: Clone, P: Default>(list: &mut Punctuated<T, P>, index: usize) {
+    *list = list.iter().enumerate()
+        .filter(|&(i, _)| i != index)
+        .map(|(_, elem)| elem.clone())
+        .collect();
+}
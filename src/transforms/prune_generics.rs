@@ -0,0 +1,130 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each generic parameter, each where clause predicate and each
+//! trait bound.
+
+use std::marker::PhantomData;
+
+use syn::{punctuated::Punctuated, token::{Add, Comma}, visit_mut::*, *};
+
+use crate::oracle::Oracle;
+use super::Transform;
+
+pub struct PruneGenerics;
+
+impl Transform for PruneGenerics {
+	fn name(&self) -> &'static str {
+		"prune-generics"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove generic parameters, where clause predicates and trait bounds"
+	}
+
+	fn run(&self, file: &mut File, oracle: &mut dyn Oracle) {
+		prune_generics(file, oracle)
+	}
+}
+
+pub fn prune_generics<O: Oracle + ?Sized>(file: &mut File, oracle: &mut O) {
+	prune_list::<GenericParam, Comma, _>(file, oracle);
+	prune_list::<WherePredicate, Comma, _>(file, oracle);
+	prune_list::<TypeParamBound, Add, _>(file, oracle);
+}
+
+fn prune_list<T, P, O: Oracle + ?Sized>(file: &mut File, oracle: &mut O)
+	where ListVisitor<T, P>: VisitMut
+{
+	super::search(file, oracle, |file, index| {
+		let mut visitor = ListVisitor {
+			changed: false,
+			cur_index: 0,
+			target_index: index,
+			_list: PhantomData,
+		};
+		visit_file_mut(&mut visitor, file);
+		// if this change is kept, the next element will be at the same index
+		if visitor.changed {
+			Some((visitor.target_index, visitor.target_index))
+		} else {
+			None
+		}
+	});
+}
+
+struct ListVisitor<T, P> {
+	changed: bool,
+	cur_index: usize,
+	target_index: usize,
+	_list: PhantomData<Punctuated<T, P>>,
+}
+
+impl<T: Clone, P: Clone + Default> ListVisitor<T, P> {
+	fn visit_list(&mut self, list: &mut Punctuated<T, P>) {
+		if self.changed {
+			return;
+		}
+
+		if self.target_index < self.cur_index + list.len() {
+			let index = self.target_index - self.cur_index;
+			*list = list.iter().enumerate()
+				.filter(|&(i, _)| i != index)
+				.map(|(_, elem)| elem.clone())
+				.collect();
+			self.changed = true;
+			return;
+		}
+		self.cur_index += list.len();
+	}
+}
+
+impl VisitMut for ListVisitor<GenericParam, Comma> {
+	fn visit_generics_mut(&mut self, i: &mut Generics) {
+		self.visit_list(&mut i.params);
+		visit_generics_mut(self, i);
+	}
+}
+
+impl VisitMut for ListVisitor<WherePredicate, Comma> {
+	fn visit_where_clause_mut(&mut self, i: &mut WhereClause) {
+		self.visit_list(&mut i.predicates);
+		visit_where_clause_mut(self, i);
+	}
+}
+
+impl VisitMut for ListVisitor<TypeParamBound, Add> {
+	fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+		self.visit_list(&mut i.supertraits);
+		visit_item_trait_mut(self, i);
+	}
+
+	fn visit_predicate_type_mut(&mut self, i: &mut PredicateType) {
+		self.visit_list(&mut i.bounds);
+		visit_predicate_type_mut(self, i);
+	}
+
+	fn visit_trait_item_type_mut(&mut self, i: &mut TraitItemType) {
+		self.visit_list(&mut i.bounds);
+		visit_trait_item_type_mut(self, i);
+	}
+
+	fn visit_type_param_mut(&mut self, i: &mut TypeParam) {
+		self.visit_list(&mut i.bounds);
+		visit_type_param_mut(self, i);
+	}
+}
@@ -15,74 +15,243 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
 
-/// Try to remove each item.
+//! Try to remove each item, including items nested in other items and in
+//! function bodies.
+//!
+//! Similar to delta debugging, each list of items is first split into halves
+//! which are tried to be removed as a whole, then quarters, etc. until single
+//! items are removed.
 
-pub fn prune_items<F: FnMut(&syn::File) -> bool>(file: &mut syn::File, mut try_compile: F) {
+use std::ops::Range;
+
+use syn::visit_mut::VisitMut;
+
+use crate::oracle::Oracle;
+use super::Transform;
+
+pub struct PruneItems;
+
+impl Transform for PruneItems {
+	fn name(&self) -> &'static str {
+		"prune-items"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove items, first in chunks and then one at a time"
+	}
+
+	fn run(&self, file: &mut syn::File, oracle: &mut dyn Oracle) {
+		prune_items(file, oracle)
+	}
+}
+
+pub fn prune_items<O: Oracle + ?Sized>(file: &mut syn::File, oracle: &mut O) {
     let mut level = 0;
-    let mut index = 0;
+    let mut chunks = 2;
+    let mut skip_single = false;
     loop {
-        let backup = file.clone();
-        if !file.items.prune(level, &mut {index}) {
-            if index == 0 {
-                break;
+        let mut last_target = None;
+        let changed = super::search(file, oracle, |file, index| {
+            let mut target = Target {
+                chunks,
+                index,
+                skip_single,
+                found_items: false,
+                splittable: false
+            };
+            let pruned = file.items.prune(level, &mut target);
+            last_target = Some(target);
+            // if this change is kept, try to delete the next chunk, which will
+            // be at the same index now that we've deleted something
+            if pruned {
+                Some((index, index))
+            } else {
+                None
             }
+        });
+
+        // the last target has visited all lists at this level
+        let target = last_target.expect("search always applies at least once");
+        if target.splittable {
+            // try again with smaller chunks
+            chunks *= 2;
+            skip_single = !changed;
+        } else if target.found_items {
             level += 1;
-            index = 0;
-            continue;
-        }
-        if !try_compile(&file) {
-            *file = backup;
-            index += 1;
+            chunks = 2;
+            skip_single = false;
         } else {
-            // try delete next, which will be at same index now that we've
-            // deleted something
+            break;
         }
     }
 }
 
+/// Which chunk of items to remove.
+struct Target {
+	/// The number of chunks each list of items is split into.
+	chunks: usize,
+	/// The index of the chunk to remove, counting all chunks of all lists at
+	/// the current level.
+	index: usize,
+	/// Whether lists that were already split into single items in the
+	/// previous round can be skipped. This is only the case if nothing was
+	/// removed in the previous round.
+	skip_single: bool,
+	/// Whether any list has items.
+	found_items: bool,
+	/// Whether any list has more items than `chunks`.
+	splittable: bool,
+}
+
+impl Target {
+	/// The range of items to remove from a list of `len` items if this list
+	/// contains the target chunk. Otherwise, subtract the number of chunks of
+	/// this list from `index`.
+	fn chunk(&mut self, len: usize) -> Option<Range<usize>> {
+		self.found_items |= len > 0;
+		self.splittable |= len > self.chunks;
+		let chunks = if self.skip_single && len <= self.chunks / 2 {
+			0
+		} else {
+			std::cmp::min(self.chunks, len)
+		};
+		if self.index < chunks {
+			Some((self.index * len / chunks)..((self.index + 1) * len / chunks))
+		} else {
+			self.index -= chunks;
+			None
+		}
+	}
+}
+
 trait Prune {
-	fn prune(&mut self, level: usize, index: &mut usize) -> bool;
+	fn prune(&mut self, level: usize, target: &mut Target) -> bool;
+}
+
+fn prune_chunk<T>(vec: &mut Vec<T>, target: &mut Target) -> bool {
+	match target.chunk(vec.len()) {
+		Some(range) => {
+			vec.drain(range);
+			true
+		},
+		None => false,
+	}
 }
 
 impl Prune for Vec<syn::Item> {
-	fn prune(&mut self, level: usize, index: &mut usize) -> bool {
+	fn prune(&mut self, level: usize, target: &mut Target) -> bool {
 		if level == 0 {
-			if *index < self.len() {
-				self.remove(*index);
-				true
-			} else {
-				*index -= self.len();
-				false
-			}
+			prune_chunk(self, target)
 		} else {
-			for item in self {
-				if match item {
-					syn::Item::Mod(syn::ItemMod { content: Some((_, items)), .. })
-						=> items.prune(level - 1, index),
-					syn::Item::Impl(syn::ItemImpl { items, .. })
-						=> items.prune(level - 1, index),
-					_ => false
-				} {
-					return true
-				}
-			}
-			false
+			self.iter_mut().any(|item| ChildPruner::prune(level - 1, target, |v| v.visit_item_mut(item)))
 		}
 	}
 }
 
 impl Prune for Vec<syn::ImplItem> {
-	fn prune(&mut self, level: usize, index: &mut usize) -> bool {
+	fn prune(&mut self, level: usize, target: &mut Target) -> bool {
 		if level == 0 {
-			if *index < self.len() {
-				self.remove(*index);
-				true
-			} else {
-				*index -= self.len();
-				false
-			}
+			prune_chunk(self, target)
+		} else {
+			self.iter_mut().any(|item| ChildPruner::prune(level - 1, target, |v| v.visit_impl_item_mut(item)))
+		}
+	}
+}
+
+impl Prune for Vec<syn::TraitItem> {
+	fn prune(&mut self, level: usize, target: &mut Target) -> bool {
+		if level == 0 {
+			prune_chunk(self, target)
+		} else {
+			self.iter_mut().any(|item| ChildPruner::prune(level - 1, target, |v| v.visit_trait_item_mut(item)))
+		}
+	}
+}
+
+impl Prune for Vec<syn::ForeignItem> {
+	fn prune(&mut self, level: usize, target: &mut Target) -> bool {
+		if level == 0 {
+			prune_chunk(self, target)
 		} else {
 			false
 		}
 	}
 }
+
+/// The statements of a block. Only item statements are pruned, but items in
+/// blocks nested within other statements are considered to be at the same
+/// level.
+impl Prune for Vec<syn::Stmt> {
+	fn prune(&mut self, level: usize, target: &mut Target) -> bool {
+		if level == 0 {
+			let items = self.iter()
+				.enumerate()
+				.filter(|(_, stmt)| matches!(stmt, syn::Stmt::Item(_)))
+				.map(|(i, _)| i)
+				.collect::<Vec<_>>();
+			if let Some(range) = target.chunk(items.len()) {
+				let remove = &items[range];
+				let mut i = 0;
+				self.retain(|_| {
+					i += 1;
+					!remove.contains(&(i - 1))
+				});
+				return true;
+			}
+		}
+
+		self.iter_mut().any(|stmt| match stmt {
+			syn::Stmt::Item(_) if level == 0 => false,
+			syn::Stmt::Item(item)
+				=> ChildPruner::prune(level - 1, target, |v| v.visit_item_mut(item)),
+			stmt
+				=> ChildPruner::prune(level, target, |v| v.visit_stmt_mut(stmt)),
+		})
+	}
+}
+
+/// Finds the item lists and blocks directly contained in an item or
+/// statement, and prunes those at `level`.
+struct ChildPruner<'a> {
+	level: usize,
+	target: &'a mut Target,
+	pruned: bool,
+}
+
+impl<'a> ChildPruner<'a> {
+	fn prune<F: FnOnce(&mut Self)>(level: usize, target: &'a mut Target, visit: F) -> bool {
+		let mut visitor = ChildPruner { level, target, pruned: false };
+		visit(&mut visitor);
+		visitor.pruned
+	}
+
+	fn prune_children<P: Prune>(&mut self, children: &mut P) {
+		if !self.pruned {
+			self.pruned = children.prune(self.level, self.target);
+		}
+	}
+}
+
+impl VisitMut for ChildPruner<'_> {
+	fn visit_block_mut(&mut self, i: &mut syn::Block) {
+		self.prune_children(&mut i.stmts)
+	}
+
+	fn visit_item_mod_mut(&mut self, i: &mut syn::ItemMod) {
+		if let Some((_, items)) = &mut i.content {
+			self.prune_children(items)
+		}
+	}
+
+	fn visit_item_impl_mut(&mut self, i: &mut syn::ItemImpl) {
+		self.prune_children(&mut i.items)
+	}
+
+	fn visit_item_trait_mut(&mut self, i: &mut syn::ItemTrait) {
+		self.prune_children(&mut i.items)
+	}
+
+	fn visit_item_foreign_mod_mut(&mut self, i: &mut syn::ItemForeignMod) {
+		self.prune_children(&mut i.items)
+	}
+}
@@ -0,0 +1,104 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each match arm. If that doesn't work, try again while adding
+//! a `_ => unimplemented!()` arm, in case the match is no longer exhaustive.
+
+use syn::visit_mut::*;
+use quote::quote;
+
+use crate::oracle::Oracle;
+use super::Transform;
+
+pub struct RemoveArms;
+
+impl Transform for RemoveArms {
+	fn name(&self) -> &'static str {
+		"remove-arms"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove match arms, adding a `_` arm if needed"
+	}
+
+	fn run(&self, file: &mut syn::File, oracle: &mut dyn Oracle) {
+		remove_arms(file, oracle)
+	}
+}
+
+pub fn remove_arms<O: Oracle + ?Sized>(file: &mut syn::File, oracle: &mut O) {
+    let fallback = syn::parse2(quote!( _ => unimplemented!(), )).unwrap();
+
+    super::search(file, oracle, |file, index| {
+        let mut visitor = ArmVisitor {
+            changed: None,
+            cur_index: 0,
+            target_index: index,
+            fallback: &fallback,
+        };
+        visit_file_mut(&mut visitor, file);
+        visitor.changed.map(|next_index| (visitor.target_index, next_index))
+    });
+}
+
+struct ArmVisitor<'a> {
+	/// If a change was made, the index of the next change to try if this
+	/// change is kept.
+	changed: Option<usize>,
+	cur_index: usize,
+	target_index: usize,
+	fallback: &'a syn::Arm,
+}
+
+fn is_catch_all(arm: &syn::Arm) -> bool {
+	matches!(arm.pat, syn::Pat::Wild(_)) && arm.guard.is_none()
+}
+
+impl VisitMut for ArmVisitor<'_> {
+	fn visit_expr_match_mut(&mut self, i: &mut syn::ExprMatch) {
+		if self.changed.is_some() {
+			return;
+		}
+
+		for arm_index in 0..i.arms.len() {
+			// each arm can be removed with or without adding a fallback arm
+			let arm_first_index = self.cur_index;
+			for add_fallback in [false, true] {
+				if self.target_index == self.cur_index {
+					let possible = !add_fallback || (
+						i.arms[arm_index] != *self.fallback
+						&& !i.arms.iter().enumerate().any(|(j, arm)| j != arm_index && is_catch_all(arm))
+					);
+					if possible {
+						i.arms.remove(arm_index);
+						if add_fallback {
+							i.arms.push(self.fallback.clone());
+						}
+						// the next arm will be at the same index
+						self.changed = Some(arm_first_index);
+						return;
+					}
+
+					self.target_index += 1;
+				}
+				self.cur_index += 1;
+			}
+		}
+
+		visit_expr_match_mut(self, i)
+	}
+}
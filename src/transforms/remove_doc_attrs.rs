@@ -15,57 +15,70 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
 
-/// Try to remove each `#[doc]` attribute (this includes doc comments).
+//! Try to remove each `#[doc]` attribute (this includes doc comments).
+
 use syn::{visit_mut::*, *};
 
-pub fn remove_doc_attrs<F: FnMut(&File) -> bool>(file: &mut File, mut try_compile: F) {
-	let mut visitor = AttrContainerVisitor {
-		backup: None,
-		cur_index: 0,
-		target_index: 1,
-	};
+use crate::oracle::Oracle;
+use super::Transform;
+
+pub struct RemoveDocAttrs;
+
+impl Transform for RemoveDocAttrs {
+	fn name(&self) -> &'static str {
+		"remove-doc-attrs"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove #[doc] attributes, including doc comments"
+	}
 
-	loop {
-		visitor.cur_index = 0;
+	fn run(&self, file: &mut File, oracle: &mut dyn Oracle) {
+		remove_doc_attrs(file, oracle)
+	}
+}
+
+pub fn remove_doc_attrs<O: Oracle + ?Sized>(file: &mut File, oracle: &mut O) {
+	super::search(file, oracle, |file, index| {
+		let mut visitor = AttrContainerVisitor {
+			changed: false,
+			cur_index: 0,
+			target_index: index,
+		};
 
 		visitor.visit_attr_container(&mut file.attrs);
 		visit_file_mut(&mut visitor, file);
 
-		// no more changes to be made
-		if visitor.backup.is_none() {
-			break;
-		}
-
-		if try_compile(file) {
-			// this change works, keep it!
-			visitor.backup = None;
+		if visitor.changed {
+			Some((visitor.target_index, visitor.target_index))
+		} else {
+			None
 		}
-	}
+	});
 }
 
 struct AttrContainerVisitor {
-	backup: Option<Vec<Attribute>>,
+	changed: bool,
 	cur_index: usize,
 	target_index: usize,
 }
 
 impl AttrContainerVisitor {
 	fn visit_attr_container(&mut self, i: &mut Vec<Attribute>) {
-		self.cur_index += 1;
+		if self.changed {
+			return;
+		}
 
 		if self.target_index == self.cur_index {
-			if let Some(backup) = self.backup.take() {
-				// the change we tried didn't work. revert and try the next
-				// possible change
-				*i = backup;
-			} else if i.iter().any(|attr| attr.path.is_ident("doc")) {
-				self.backup = Some(i.clone());
+			if i.iter().any(|attr| attr.path.is_ident("doc")) {
 				i.retain(|attr| !attr.path.is_ident("doc"));
+				self.changed = true;
 				return;
 			}
 
 			self.target_index += 1;
 		}
+		self.cur_index += 1;
 	}
 }
 
@@ -0,0 +1,299 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each struct field, along with the corresponding field in
+//! struct expressions and patterns.
+//!
+//! Uses of the struct are found by its name, or by `Self` in its `impl`
+//! blocks. Field accesses can only be matched to the struct without type
+//! information if they are on `self` in its `impl` blocks, or on a local
+//! binding whose type annotation or initializer names the struct. Only those
+//! accesses to later fields of a tuple struct are renumbered.
+
+use syn::{visit_mut::*, *};
+
+use crate::oracle::Oracle;
+use super::{remove_at, Transform};
+
+pub struct RemoveFields;
+
+impl Transform for RemoveFields {
+	fn name(&self) -> &'static str {
+		"remove-fields"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove struct fields and the corresponding initializers and patterns"
+	}
+
+	fn run(&self, file: &mut File, oracle: &mut dyn Oracle) {
+		remove_fields(file, oracle)
+	}
+}
+
+pub fn remove_fields<O: Oracle + ?Sized>(file: &mut File, oracle: &mut O) {
+	super::search(file, oracle, |file, index| {
+		let mut visitor = FieldVisitor {
+			removed: None,
+			cur_index: 0,
+			target_index: index,
+		};
+		visit_file_mut(&mut visitor, file);
+		let removed = visitor.removed?;
+		visit_file_mut(&mut UseVisitor { removed, in_impl: false, bindings: vec![] }, file);
+		// if this change is kept, the next field will be at the same index
+		Some((visitor.target_index, visitor.target_index))
+	});
+}
+
+/// A field that was removed.
+struct Removed {
+	/// The name of the struct.
+	strukt: Ident,
+	/// The name or position of the field.
+	member: Member,
+	/// The number of fields the struct had.
+	fields: usize,
+}
+
+impl Removed {
+	/// The position of the field in a tuple struct.
+	fn position(&self) -> Option<usize> {
+		match &self.member {
+			Member::Unnamed(index) => Some(index.index as usize),
+			Member::Named(_) => None,
+		}
+	}
+
+	/// Updates `member` for the removed field, returns whether it is the
+	/// removed field.
+	fn update_member(&self, member: &mut Member) -> bool {
+		match (&self.member, member) {
+			(Member::Named(removed), Member::Named(ident)) => removed == ident,
+			(Member::Unnamed(removed), Member::Unnamed(index)) => {
+				if index.index > removed.index {
+					index.index -= 1;
+					return false;
+				}
+				index.index == removed.index
+			},
+			_ => false,
+		}
+	}
+}
+
+struct FieldVisitor {
+	removed: Option<Removed>,
+	cur_index: usize,
+	target_index: usize,
+}
+
+impl VisitMut for FieldVisitor {
+	fn visit_item_struct_mut(&mut self, i: &mut ItemStruct) {
+		if self.removed.is_some() {
+			return;
+		}
+
+		let fields = match &mut i.fields {
+			Fields::Named(FieldsNamed { named: fields, .. }) |
+			Fields::Unnamed(FieldsUnnamed { unnamed: fields, .. }) => fields,
+			Fields::Unit => return,
+		};
+		if self.target_index < self.cur_index + fields.len() {
+			let position = self.target_index - self.cur_index;
+			let member = match &fields[position].ident {
+				Some(ident) => Member::Named(ident.clone()),
+				None => Member::Unnamed(position.into()),
+			};
+			self.removed = Some(Removed { strukt: i.ident.clone(), member, fields: fields.len() });
+			remove_at(fields, position);
+			return;
+		}
+		self.cur_index += fields.len();
+		visit_item_struct_mut(self, i);
+	}
+}
+
+/// Removes the removed field from each use of the struct.
+struct UseVisitor {
+	removed: Removed,
+	/// Whether `Self` refers to the struct.
+	in_impl: bool,
+	/// The local bindings in scope that hold the struct.
+	bindings: Vec<Ident>,
+}
+
+impl UseVisitor {
+	fn is_struct(&self, path: &Path) -> bool {
+		path.segments.last().is_some_and(|segment| {
+			segment.ident == self.removed.strukt || (self.in_impl && segment.ident == "Self" && path.segments.len() == 1)
+		})
+	}
+
+	/// Whether `ty` is the struct or a reference to it.
+	fn is_struct_type(&self, ty: &Type) -> bool {
+		match ty {
+			Type::Path(TypePath { path, .. }) => self.is_struct(path),
+			Type::Reference(TypeReference { elem, .. }) | Type::Paren(TypeParen { elem, .. }) => self.is_struct_type(elem),
+			_ => false,
+		}
+	}
+
+	/// Whether `expr` constructs the struct, or calls one of its associated
+	/// functions, which likely returns it.
+	fn is_struct_expr(&self, expr: &Expr) -> bool {
+		match expr {
+			Expr::Struct(ExprStruct { path, .. }) => self.is_struct(path),
+			Expr::Call(ExprCall { func, .. }) => match &**func {
+				Expr::Path(ExprPath { path, .. }) => {
+					let parent = Path {
+						leading_colon: path.leading_colon,
+						segments: path.segments.iter().take(path.segments.len().saturating_sub(1)).cloned().collect(),
+					};
+					self.is_struct(path) || self.is_struct(&parent)
+				},
+				_ => false,
+			},
+			Expr::Reference(ExprReference { expr, .. }) | Expr::Paren(ExprParen { expr, .. }) => self.is_struct_expr(expr),
+			_ => false,
+		}
+	}
+
+	fn in_scope<F: FnOnce(&mut Self)>(&mut self, visit: F) {
+		let bindings = self.bindings.clone();
+		visit(self);
+		self.bindings = bindings;
+	}
+}
+
+impl VisitMut for UseVisitor {
+	fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+		let in_impl = self.in_impl;
+		self.in_impl = match &*i.self_ty {
+			Type::Path(TypePath { path, .. }) => path.segments.last().is_some_and(|segment| segment.ident == self.removed.strukt),
+			_ => false,
+		};
+		visit_item_impl_mut(self, i);
+		self.in_impl = in_impl;
+	}
+
+	fn visit_expr_struct_mut(&mut self, i: &mut ExprStruct) {
+		if self.is_struct(&i.path) {
+			let removed = &self.removed;
+			i.fields = i.fields.iter()
+				.cloned()
+				.filter_map(|mut field| if removed.update_member(&mut field.member) { None } else { Some(field) })
+				.collect();
+		}
+		visit_expr_struct_mut(self, i);
+	}
+
+	fn visit_pat_struct_mut(&mut self, i: &mut PatStruct) {
+		if self.is_struct(&i.path) {
+			let removed = &self.removed;
+			i.fields = i.fields.iter()
+				.cloned()
+				.filter_map(|mut field| if removed.update_member(&mut field.member) { None } else { Some(field) })
+				.collect();
+		}
+		visit_pat_struct_mut(self, i);
+	}
+
+	fn visit_expr_call_mut(&mut self, i: &mut ExprCall) {
+		if let (Expr::Path(ExprPath { path, .. }), Some(position)) = (&*i.func, self.removed.position()) {
+			if self.is_struct(path) && position < i.args.len() {
+				remove_at(&mut i.args, position);
+			}
+		}
+		visit_expr_call_mut(self, i);
+	}
+
+	fn visit_pat_tuple_struct_mut(&mut self, i: &mut PatTupleStruct) {
+		if let Some(position) = self.removed.position() {
+			if self.is_struct(&i.path) {
+				let elems = &mut i.pat.elems;
+				// fields after `..` are matched from the end
+				let index = match elems.iter().position(|pat| matches!(pat, Pat::Rest(_))) {
+					Some(rest) if position >= rest => (elems.len() + position).checked_sub(self.removed.fields).filter(|&index| index > rest),
+					_ => Some(position),
+				};
+				if let Some(index) = index.filter(|&index| index < elems.len()) {
+					remove_at(elems, index);
+				}
+			}
+		}
+		visit_pat_tuple_struct_mut(self, i);
+	}
+
+	fn visit_expr_field_mut(&mut self, i: &mut ExprField) {
+		if let Expr::Path(ExprPath { path, .. }) = &*i.base {
+			let holds_struct = match path.get_ident() {
+				Some(ident) if ident == "self" => self.in_impl,
+				Some(ident) => self.bindings.contains(ident),
+				None => false,
+			};
+			if holds_struct {
+				self.removed.update_member(&mut i.member);
+			}
+		}
+		visit_expr_field_mut(self, i);
+	}
+
+	fn visit_pat_ident_mut(&mut self, i: &mut PatIdent) {
+		// shadowed
+		self.bindings.retain(|binding| *binding != i.ident);
+		visit_pat_ident_mut(self, i);
+	}
+
+	fn visit_pat_type_mut(&mut self, i: &mut PatType) {
+		visit_pat_type_mut(self, i);
+		if let Pat::Ident(PatIdent { ident, .. }) = &*i.pat {
+			if self.is_struct_type(&i.ty) {
+				self.bindings.push(ident.clone());
+			}
+		}
+	}
+
+	fn visit_local_mut(&mut self, i: &mut Local) {
+		visit_local_mut(self, i);
+		if let (Pat::Ident(PatIdent { ident, .. }), Some((_, init))) = (&i.pat, &i.init) {
+			if self.is_struct_expr(init) {
+				self.bindings.push(ident.clone());
+			}
+		}
+	}
+
+	fn visit_block_mut(&mut self, i: &mut Block) {
+		self.in_scope(|this| visit_block_mut(this, i));
+	}
+
+	fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+		self.in_scope(|this| visit_item_fn_mut(this, i));
+	}
+
+	fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+		self.in_scope(|this| visit_impl_item_method_mut(this, i));
+	}
+
+	fn visit_trait_item_method_mut(&mut self, i: &mut TraitItemMethod) {
+		self.in_scope(|this| visit_trait_item_method_mut(this, i));
+	}
+
+	fn visit_expr_closure_mut(&mut self, i: &mut ExprClosure) {
+		self.in_scope(|this| visit_expr_closure_mut(this, i));
+	}
+}
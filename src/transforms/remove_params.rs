@@ -0,0 +1,132 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each function parameter, along with the corresponding
+//! argument at each call site.
+//!
+//! Call sites are found by the name of the function, so calls through other
+//! names aren't updated and calls to other functions with the same name are
+//! updated as well. The oracle decides whether that still works.
+
+use syn::{visit_mut::*, *};
+
+use crate::oracle::Oracle;
+use super::{remove_at, Transform};
+
+pub struct RemoveParams;
+
+impl Transform for RemoveParams {
+	fn name(&self) -> &'static str {
+		"remove-params"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove function parameters and the corresponding call arguments"
+	}
+
+	fn run(&self, file: &mut File, oracle: &mut dyn Oracle) {
+		remove_params(file, oracle)
+	}
+}
+
+pub fn remove_params<O: Oracle + ?Sized>(file: &mut File, oracle: &mut O) {
+	super::search(file, oracle, |file, index| {
+		let mut visitor = ParamVisitor {
+			removed: None,
+			cur_index: 0,
+			target_index: index,
+		};
+		visit_file_mut(&mut visitor, file);
+		let removed = visitor.removed?;
+		visit_file_mut(&mut CallVisitor { removed }, file);
+		// if this change is kept, the next parameter will be at the same index
+		Some((visitor.target_index, visitor.target_index))
+	});
+}
+
+/// A parameter that was removed.
+struct Removed {
+	/// The name of the function.
+	function: Ident,
+	/// The position of the parameter, not counting `self`.
+	position: usize,
+	/// Whether the function has a `self` parameter, which is the first
+	/// argument when it's called with a path.
+	receiver: bool,
+}
+
+struct ParamVisitor {
+	removed: Option<Removed>,
+	cur_index: usize,
+	target_index: usize,
+}
+
+impl ParamVisitor {
+	fn visit_signature(&mut self, sig: &mut Signature) {
+		if self.removed.is_some() {
+			return;
+		}
+
+		let receiver = matches!(sig.inputs.first(), Some(FnArg::Receiver(_)));
+		let params = sig.inputs.len() - receiver as usize;
+		if self.target_index < self.cur_index + params {
+			let position = self.target_index - self.cur_index;
+			remove_at(&mut sig.inputs, position + receiver as usize);
+			self.removed = Some(Removed { function: sig.ident.clone(), position, receiver });
+			return;
+		}
+		self.cur_index += params;
+	}
+}
+
+impl VisitMut for ParamVisitor {
+	fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+		self.visit_signature(&mut i.sig);
+		visit_item_fn_mut(self, i);
+	}
+
+	fn visit_impl_item_method_mut(&mut self, i: &mut ImplItemMethod) {
+		self.visit_signature(&mut i.sig);
+		visit_impl_item_method_mut(self, i);
+	}
+}
+
+/// Removes the argument for the removed parameter from each call.
+struct CallVisitor {
+	removed: Removed,
+}
+
+impl VisitMut for CallVisitor {
+	fn visit_expr_call_mut(&mut self, i: &mut ExprCall) {
+		if let Expr::Path(ExprPath { path, .. }) = &*i.func {
+			if path.segments.last().is_some_and(|segment| segment.ident == self.removed.function) {
+				let position = self.removed.position + self.removed.receiver as usize;
+				if position < i.args.len() {
+					remove_at(&mut i.args, position);
+				}
+			}
+		}
+		visit_expr_call_mut(self, i);
+	}
+
+	fn visit_expr_method_call_mut(&mut self, i: &mut ExprMethodCall) {
+		if self.removed.receiver && i.method == self.removed.function && self.removed.position < i.args.len() {
+			remove_at(&mut i.args, self.removed.position);
+		}
+		visit_expr_method_call_mut(self, i);
+	}
+}
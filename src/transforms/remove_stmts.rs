@@ -0,0 +1,80 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each statement from each block.
+
+use syn::visit_mut::*;
+
+use crate::oracle::Oracle;
+use super::Transform;
+
+pub struct RemoveStmts;
+
+impl Transform for RemoveStmts {
+	fn name(&self) -> &'static str {
+		"remove-stmts"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove statements from blocks"
+	}
+
+	fn run(&self, file: &mut syn::File, oracle: &mut dyn Oracle) {
+		remove_stmts(file, oracle)
+	}
+}
+
+pub fn remove_stmts<O: Oracle + ?Sized>(file: &mut syn::File, oracle: &mut O) {
+    super::search(file, oracle, |file, index| {
+        let mut visitor = StmtVisitor {
+            changed: false,
+            cur_index: 0,
+            target_index: index,
+        };
+        visit_file_mut(&mut visitor, file);
+        // if this change is kept, the next statement will be at the same
+        // index
+        if visitor.changed {
+            Some((visitor.target_index, visitor.target_index))
+        } else {
+            None
+        }
+    });
+}
+
+struct StmtVisitor {
+	changed: bool,
+	cur_index: usize,
+	target_index: usize,
+}
+
+impl VisitMut for StmtVisitor {
+	fn visit_block_mut(&mut self, i: &mut syn::Block) {
+		let mut stmt_index = 0;
+		while stmt_index < i.stmts.len() && !self.changed {
+			if self.target_index == self.cur_index {
+				i.stmts.remove(stmt_index);
+				self.changed = true;
+				return;
+			}
+			self.cur_index += 1;
+
+			visit_stmt_mut(self, &mut i.stmts[stmt_index]);
+			stmt_index += 1;
+		}
+	}
+}
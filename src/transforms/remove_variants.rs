@@ -0,0 +1,226 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to remove each enum variant, along with the match arms that match it.
+//! Expressions that construct the variant are replaced with
+//! `unimplemented!()`.
+//!
+//! An `if let` that matches the variant is replaced with its `else` branch, a
+//! `while let` is dropped, and any other `let` expression becomes `false`. A
+//! `let` statement that matches the variant binds the same names to
+//! `unimplemented!()` instead.
+//!
+//! Uses of the variant are found by paths ending in `Enum::Variant`, or
+//! `Self::Variant` in the enum's `impl` blocks. Variants that are imported
+//! and used by their name alone aren't found.
+
+use syn::{visit_mut::*, *};
+use quote::quote;
+
+use crate::oracle::Oracle;
+use super::{remove_at, Transform};
+
+pub struct RemoveVariants;
+
+impl Transform for RemoveVariants {
+	fn name(&self) -> &'static str {
+		"remove-variants"
+	}
+
+	fn description(&self) -> &'static str {
+		"Remove enum variants and the match arms and constructors that use them"
+	}
+
+	fn run(&self, file: &mut File, oracle: &mut dyn Oracle) {
+		remove_variants(file, oracle)
+	}
+}
+
+pub fn remove_variants<O: Oracle + ?Sized>(file: &mut File, oracle: &mut O) {
+	let unimplemented = syn::parse2(quote!( unimplemented!() )).unwrap();
+
+	super::search(file, oracle, |file, index| {
+		let mut visitor = VariantVisitor {
+			removed: None,
+			cur_index: 0,
+			target_index: index,
+		};
+		visit_file_mut(&mut visitor, file);
+		let removed = visitor.removed?;
+		visit_file_mut(&mut UseVisitor { removed, in_impl: false, unimplemented: &unimplemented }, file);
+		// if this change is kept, the next variant will be at the same index
+		Some((visitor.target_index, visitor.target_index))
+	});
+}
+
+/// A variant that was removed.
+struct Removed {
+	/// The name of the enum.
+	enum_name: Ident,
+	/// The name of the variant.
+	variant: Ident,
+}
+
+struct VariantVisitor {
+	removed: Option<Removed>,
+	cur_index: usize,
+	target_index: usize,
+}
+
+impl VisitMut for VariantVisitor {
+	fn visit_item_enum_mut(&mut self, i: &mut ItemEnum) {
+		if self.removed.is_some() {
+			return;
+		}
+
+		if self.target_index < self.cur_index + i.variants.len() {
+			let position = self.target_index - self.cur_index;
+			let variant = i.variants[position].ident.clone();
+			self.removed = Some(Removed { enum_name: i.ident.clone(), variant });
+			remove_at(&mut i.variants, position);
+			return;
+		}
+		self.cur_index += i.variants.len();
+		visit_item_enum_mut(self, i);
+	}
+}
+
+/// Removes the match arms for the removed variant and replaces its
+/// constructors.
+struct UseVisitor<'a> {
+	removed: Removed,
+	/// Whether `Self` refers to the enum.
+	in_impl: bool,
+	unimplemented: &'a Expr,
+}
+
+impl UseVisitor<'_> {
+	fn is_variant(&self, path: &Path) -> bool {
+		let mut segments = path.segments.iter().rev();
+		match (segments.next(), segments.next()) {
+			(Some(variant), Some(enum_name)) => variant.ident == self.removed.variant && (
+				enum_name.ident == self.removed.enum_name
+				|| (self.in_impl && enum_name.ident == "Self" && path.segments.len() == 2)
+			),
+			_ => false,
+		}
+	}
+
+	fn mentions_variant(&self, pat: &mut Pat) -> bool {
+		let mut finder = PathFinder { visitor: self, found: false };
+		finder.visit_pat_mut(pat);
+		finder.found
+	}
+}
+
+impl VisitMut for UseVisitor<'_> {
+	fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+		let in_impl = self.in_impl;
+		self.in_impl = match &*i.self_ty {
+			Type::Path(TypePath { path, .. }) => path.segments.last().is_some_and(|segment| segment.ident == self.removed.enum_name),
+			_ => false,
+		};
+		visit_item_impl_mut(self, i);
+		self.in_impl = in_impl;
+	}
+
+	fn visit_expr_match_mut(&mut self, i: &mut ExprMatch) {
+		i.arms.retain_mut(|arm| {
+			// remove just the matching cases of `A | B` patterns
+			if let Pat::Or(or) = &mut arm.pat {
+				or.cases = or.cases.iter()
+					.cloned()
+					.filter_map(|mut case| if self.mentions_variant(&mut case) { None } else { Some(case) })
+					.collect();
+				!or.cases.is_empty()
+			} else {
+				!self.mentions_variant(&mut arm.pat)
+			}
+		});
+		visit_expr_match_mut(self, i);
+	}
+
+	fn visit_local_mut(&mut self, i: &mut Local) {
+		if self.mentions_variant(&mut i.pat) {
+			let mut bindings = BindingFinder { bindings: vec![] };
+			bindings.visit_pat_mut(&mut i.pat);
+			let bindings = bindings.bindings;
+			i.pat = parse_quote!( (#(#bindings,)*) );
+			i.init = Some((Default::default(), Box::new(self.unimplemented.clone())));
+			return;
+		}
+		visit_local_mut(self, i);
+	}
+
+	fn visit_expr_mut(&mut self, i: &mut Expr) {
+		// a pattern that matches the variant never matches anymore
+		let never_matches = match i {
+			Expr::If(ExprIf { cond, .. }) | Expr::While(ExprWhile { cond, .. }) => match &mut **cond {
+				Expr::Let(ExprLet { pat, .. }) => self.mentions_variant(pat),
+				_ => false,
+			},
+			Expr::Let(ExprLet { pat, .. }) => self.mentions_variant(pat),
+			_ => false,
+		};
+		if never_matches {
+			*i = match i {
+				Expr::If(ExprIf { else_branch: Some((_, else_branch)), .. }) => (**else_branch).clone(),
+				Expr::If(_) | Expr::While(_) => parse_quote!( {} ),
+				_ => parse_quote!( false ),
+			};
+			// the `else` branch may be another `if let`
+			self.visit_expr_mut(i);
+			return;
+		}
+
+		let constructor = match i {
+			Expr::Path(ExprPath { path, .. }) | Expr::Struct(ExprStruct { path, .. }) => self.is_variant(path),
+			Expr::Call(ExprCall { func, .. }) => matches!(&**func, Expr::Path(ExprPath { path, .. }) if self.is_variant(path)),
+			_ => false,
+		};
+		if constructor {
+			*i = self.unimplemented.clone();
+			return;
+		}
+		visit_expr_mut(self, i);
+	}
+}
+
+/// Looks for paths to the removed variant.
+struct PathFinder<'a, 'b> {
+	visitor: &'a UseVisitor<'b>,
+	found: bool,
+}
+
+impl VisitMut for PathFinder<'_, '_> {
+	fn visit_path_mut(&mut self, i: &mut Path) {
+		self.found |= self.visitor.is_variant(i);
+		visit_path_mut(self, i);
+	}
+}
+
+/// Collects the names bound by a pattern.
+struct BindingFinder {
+	bindings: Vec<PatIdent>,
+}
+
+impl VisitMut for BindingFinder {
+	fn visit_pat_ident_mut(&mut self, i: &mut PatIdent) {
+		self.bindings.push(PatIdent { subpat: None, ..i.clone() });
+		visit_pat_ident_mut(self, i);
+	}
+}
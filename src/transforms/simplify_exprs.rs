@@ -0,0 +1,133 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Try to replace each expression with one of its subexpressions, e.g.
+//! `a + b` with `a`, or `if c { a } else { b }` with `{ a }`.
+
+use syn::{visit_mut::*, Expr};
+
+use crate::oracle::Oracle;
+use super::Transform;
+
+pub struct SimplifyExprs;
+
+impl Transform for SimplifyExprs {
+	fn name(&self) -> &'static str {
+		"simplify-exprs"
+	}
+
+	fn description(&self) -> &'static str {
+		"Replace expressions with one of their subexpressions"
+	}
+
+	fn run(&self, file: &mut syn::File, oracle: &mut dyn Oracle) {
+		simplify_exprs(file, oracle)
+	}
+}
+
+pub fn simplify_exprs<O: Oracle + ?Sized>(file: &mut syn::File, oracle: &mut O) {
+    super::search(file, oracle, |file, index| {
+        let mut visitor = ExprVisitor {
+            changed: None,
+            cur_index: 0,
+            target_index: index,
+        };
+        visit_file_mut(&mut visitor, file);
+        visitor.changed.map(|next_index| (visitor.target_index, next_index))
+    });
+}
+
+struct ExprVisitor {
+	/// If a change was made, the index of the next change to try if this
+	/// change is kept.
+	changed: Option<usize>,
+	cur_index: usize,
+	target_index: usize,
+}
+
+fn block_expr(block: &syn::Block) -> Expr {
+	Expr::Block(syn::ExprBlock {
+		attrs: vec![],
+		label: None,
+		block: block.clone(),
+	})
+}
+
+/// The subexpressions that `expr` may be replaced with.
+fn subexprs(expr: &Expr) -> Vec<Expr> {
+	match expr {
+		Expr::Array(e) => e.elems.iter().cloned().collect(),
+		Expr::Assign(e) => vec![(*e.left).clone(), (*e.right).clone()],
+		Expr::AssignOp(e) => vec![(*e.left).clone(), (*e.right).clone()],
+		Expr::Await(e) => vec![(*e.base).clone()],
+		Expr::Binary(e) => vec![(*e.left).clone(), (*e.right).clone()],
+		Expr::Block(syn::ExprBlock { block, .. }) => match &block.stmts[..] {
+			[syn::Stmt::Expr(e)] => vec![e.clone()],
+			_ => vec![],
+		},
+		Expr::Box(e) => vec![(*e.expr).clone()],
+		Expr::Call(e) => e.args.iter().cloned().collect(),
+		Expr::Cast(e) => vec![(*e.expr).clone()],
+		Expr::Field(e) => vec![(*e.base).clone()],
+		Expr::Group(e) => vec![(*e.expr).clone()],
+		Expr::If(e) => {
+			let mut v = vec![block_expr(&e.then_branch)];
+			if let Some((_, else_branch)) = &e.else_branch {
+				v.push((**else_branch).clone());
+			}
+			v
+		},
+		Expr::Index(e) => vec![(*e.expr).clone(), (*e.index).clone()],
+		Expr::Match(e) => e.arms.iter().map(|arm| (*arm.body).clone()).collect(),
+		Expr::MethodCall(e) => {
+			Some((*e.receiver).clone()).into_iter().chain(e.args.iter().cloned()).collect()
+		},
+		Expr::Paren(e) => vec![(*e.expr).clone()],
+		Expr::Range(e) => e.from.iter().chain(&e.to).map(|e| (**e).clone()).collect(),
+		Expr::Reference(e) => vec![(*e.expr).clone()],
+		Expr::Repeat(e) => vec![(*e.expr).clone()],
+		Expr::Try(e) => vec![(*e.expr).clone()],
+		Expr::Tuple(e) => e.elems.iter().cloned().collect(),
+		Expr::Type(e) => vec![(*e.expr).clone()],
+		Expr::Unary(e) => vec![(*e.expr).clone()],
+		Expr::Unsafe(e) => vec![block_expr(&e.block)],
+		_ => vec![],
+	}
+}
+
+impl VisitMut for ExprVisitor {
+	fn visit_expr_mut(&mut self, i: &mut Expr) {
+		if self.changed.is_some() {
+			return;
+		}
+
+		let first_index = self.cur_index;
+		for sub in subexprs(i) {
+			if self.target_index == self.cur_index {
+				*i = sub;
+				// try simplifying the replacement further, its changes are
+				// numbered starting where those of the original expression
+				// started
+				self.changed = Some(first_index);
+				return;
+			}
+			self.cur_index += 1;
+		}
+
+		visit_expr_mut(self, i)
+	}
+}
@@ -0,0 +1,276 @@
+// Copyright (c) Jethro G. Beekman
+//
+// This file is part of rust-reduce.
+//
+// rust-reduce is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-reduce is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Vendor the source of a dependency into the input as a module, so that the
+//! passes can reduce both together.
+
+use std::{fmt, fs, path::{Path, PathBuf}};
+
+use proc_macro2::{Ident, Punct, Spacing, Span, TokenStream, TokenTree};
+use syn::{parse_quote, visit_mut::{self, VisitMut}};
+use toml_edit::{DocumentMut, Item};
+
+use crate::InlineError;
+
+/// Why a dependency couldn't be vendored.
+#[derive(Debug)]
+pub enum Error {
+    /// The library of the dependency wasn't found at this path.
+    NoLibrary(PathBuf),
+    /// The dependency refers to module files that are missing.
+    Inline(InlineError),
+    /// The dependency at this path inherits its edition from a workspace
+    /// that wasn't found.
+    WorkspaceEdition(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoLibrary(path) => write!(f, "library not found: {}", path.display()),
+            Error::Inline(InlineError::NotFound(missing)) => {
+                write!(f, "module file not found:")?;
+                for (modname, loc) in missing {
+                    write!(f, " mod {} @ {}:{}", modname, loc.path.display(), loc.line)?;
+                }
+                Ok(())
+            },
+            Error::Inline(e) => write!(f, "{:?}", e),
+            Error::WorkspaceEdition(path) => write!(f, "edition inherited from a workspace that wasn't found: {}", path.display()),
+        }
+    }
+}
+
+/// Vendor the dependency `name` from `path` into `file`. `path` is the
+/// directory of the dependency's package, e.g. from `cargo vendor`, or its
+/// library root file.
+///
+/// The library is added as `mod name` at the start of `file`. `extern crate
+/// name` items at the root of `file` are removed, and paths in `file` that
+/// start with the dependency are rewritten to start with `crate::name`.
+pub fn vendor(file: &mut syn::File, name: &str, path: &Path) -> Result<(), Error> {
+    let name = Ident::new(&name.replace('-', "_"), Span::call_site());
+    let (lib, edition_2015) = if path.is_file() {
+        (path.to_owned(), false)
+    } else {
+        let manifest = fs::read_to_string(path.join("Cargo.toml")).ok()
+            .and_then(|manifest| manifest.parse::<DocumentMut>().ok());
+        let setting = |table: &str, key: &str| manifest.as_ref()
+            .and_then(|manifest| manifest.get(table)?.get(key)?.as_str().map(ToOwned::to_owned));
+        let lib = path.join(setting("lib", "path").unwrap_or_else(|| "src/lib.rs".into()));
+        let edition = match manifest.as_ref().and_then(|manifest| manifest.get("package")?.get("edition")) {
+            Some(edition) if edition.get("workspace").and_then(Item::as_bool) == Some(true) => {
+                Some(workspace_edition(path).ok_or_else(|| Error::WorkspaceEdition(path.to_owned()))?)
+            },
+            edition => edition.and_then(Item::as_str).map(ToOwned::to_owned),
+        };
+        // the edition defaults to 2015 if not specified in the manifest
+        (lib, manifest.is_some() && edition.is_none_or(|edition| edition == "2015"))
+    };
+    if !lib.is_file() {
+        return Err(Error::NoLibrary(lib));
+    }
+
+    let mut dependency = crate::inline_modules(&lib).map_err(Error::Inline)?;
+    DependencyRewriter { name: name.clone(), edition_2015 }.visit_file_mut(&mut dependency);
+
+    let mut rewriter = UseRewriter { names: vec![name.clone()] };
+    let mut macro_use = false;
+    file.items.retain_mut(|item| match item {
+        syn::Item::ExternCrate(krate) if krate.ident == name => {
+            macro_use |= krate.attrs.iter().any(|attr| attr.path.is_ident("macro_use"));
+            match krate.rename.clone() {
+                Some((_, alias)) => {
+                    let vis = &krate.vis;
+                    *item = parse_quote!(#vis use crate::#name as #alias;);
+                    rewriter.names.push(alias);
+                    true
+                },
+                None => false,
+            }
+        },
+        _ => true,
+    });
+    rewriter.visit_file_mut(file);
+
+    // crate attributes other than lints aren't allowed on modules
+    let lints = ["allow", "warn", "deny", "forbid"];
+    let attrs = dependency.attrs.into_iter()
+        .filter(|attr| lints.iter().any(|lint| attr.path.is_ident(lint)));
+    let items = dependency.items;
+    let macro_use = if macro_use { Some(quote::quote!(#[macro_use])) } else { None };
+    file.items.insert(0, parse_quote! {
+        #macro_use
+        pub mod #name {
+            #(#attrs)*
+            #(#items)*
+        }
+    });
+    Ok(())
+}
+
+/// The edition in the `[workspace.package]` table of the workspace that
+/// contains the package in `dir`, if any.
+fn workspace_edition(dir: &Path) -> Option<String> {
+    let dir = fs::canonicalize(dir).ok()?;
+    let workspace = dir.ancestors().skip(1).find_map(|dir| {
+        let manifest = fs::read_to_string(dir.join("Cargo.toml")).ok()?.parse::<DocumentMut>().ok()?;
+        manifest.get("workspace").cloned()
+    })?;
+    workspace.get("package")?.get("edition")?.as_str().map(ToOwned::to_owned)
+}
+
+/// Rewrites paths that start with one of `names` to start with `crate`.
+struct UseRewriter {
+    names: Vec<Ident>,
+}
+
+impl UseRewriter {
+    fn rewrite_use_tree(&self, tree: &mut syn::UseTree) -> bool {
+        match tree {
+            syn::UseTree::Group(group) => {
+                group.items.iter_mut().fold(false, |rewritten, tree| self.rewrite_use_tree(tree) | rewritten)
+            },
+            tree if first_ident(tree).is_some_and(|ident| self.names.contains(ident)) => {
+                let rest = tree.clone();
+                *tree = parse_quote!(crate::#rest);
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+impl VisitMut for UseRewriter {
+    fn visit_item_use_mut(&mut self, i: &mut syn::ItemUse) {
+        if self.rewrite_use_tree(&mut i.tree) {
+            i.leading_colon = None;
+        }
+    }
+
+    fn visit_path_mut(&mut self, i: &mut syn::Path) {
+        if self.names.contains(&i.segments[0].ident) && (i.leading_colon.is_some() || i.segments.len() > 1) {
+            i.leading_colon = None;
+            i.segments.insert(0, Ident::new("crate", Span::call_site()).into());
+        }
+        visit_mut::visit_path_mut(self, i);
+    }
+}
+
+fn first_ident(tree: &syn::UseTree) -> Option<&Ident> {
+    match tree {
+        syn::UseTree::Path(syn::UsePath { ident, .. })
+        | syn::UseTree::Name(syn::UseName { ident })
+        | syn::UseTree::Rename(syn::UseRename { ident, .. }) => Some(ident),
+        syn::UseTree::Glob(_) | syn::UseTree::Group(_) => None,
+    }
+}
+
+/// Rewrites paths within the dependency that are relative to its crate root
+/// to be relative to `crate::name`.
+struct DependencyRewriter {
+    name: Ident,
+    /// In the 2015 edition, paths in `use` items and paths starting with
+    /// `::` are relative to the crate root.
+    edition_2015: bool,
+}
+
+impl DependencyRewriter {
+    fn is_root_relative(&self, ident: &Ident) -> bool {
+        ident == "crate" || (self.edition_2015 && !["self", "super", "std", "core", "alloc"].iter().any(|name| ident == name))
+    }
+
+    fn rewrite_use_tree(&self, tree: &mut syn::UseTree) {
+        match tree {
+            syn::UseTree::Path(path) if path.ident == "crate" => {
+                let name = &self.name;
+                let rest = &path.tree;
+                *path.tree = parse_quote!(#name::#rest);
+            },
+            tree if first_ident(tree).is_some_and(|ident| self.is_root_relative(ident)) => {
+                let name = &self.name;
+                let rest = tree.clone();
+                *tree = parse_quote!(crate::#name::#rest);
+            },
+            syn::UseTree::Group(group) => group.items.iter_mut().for_each(|tree| self.rewrite_use_tree(tree)),
+            _ => {},
+        }
+    }
+}
+
+impl VisitMut for DependencyRewriter {
+    fn visit_item_use_mut(&mut self, i: &mut syn::ItemUse) {
+        if i.leading_colon.is_some() && !self.edition_2015 {
+            return;
+        }
+        i.leading_colon = None;
+        self.rewrite_use_tree(&mut i.tree);
+    }
+
+    fn visit_path_mut(&mut self, i: &mut syn::Path) {
+        if i.leading_colon.is_some() && self.edition_2015 && self.is_root_relative(&i.segments[0].ident) {
+            i.leading_colon = None;
+            i.segments.insert(0, Ident::new("crate", Span::call_site()).into());
+            i.segments.insert(1, self.name.clone().into());
+        } else if i.leading_colon.is_none() && i.segments.len() > 1 && i.segments[0].ident == "crate" {
+            i.segments.insert(1, self.name.clone().into());
+        }
+        visit_mut::visit_path_mut(self, i);
+    }
+
+    fn visit_vis_restricted_mut(&mut self, i: &mut syn::VisRestricted) {
+        // `pub(crate)`, `pub(self)` and `pub(super)` stay the same
+        if i.in_token.is_some() {
+            self.visit_path_mut(&mut i.path);
+        }
+    }
+
+    fn visit_macro_mut(&mut self, i: &mut syn::Macro) {
+        i.tokens = rewrite_dollar_crate(i.tokens.clone(), &self.name);
+        visit_mut::visit_macro_mut(self, i);
+    }
+}
+
+/// Rewrite `$crate::` in a macro definition to `$crate::name::`.
+fn rewrite_dollar_crate(tokens: TokenStream, name: &Ident) -> TokenStream {
+    let mut out = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) => {
+                let mut new = proc_macro2::Group::new(group.delimiter(), rewrite_dollar_crate(group.stream(), name));
+                new.set_span(group.span());
+                out.push(TokenTree::Group(new));
+            },
+            TokenTree::Punct(ref dollar) if dollar.as_char() == '$' => {
+                out.push(token);
+                if let Some(TokenTree::Ident(ident)) = tokens.peek() {
+                    if ident == "crate" {
+                        out.push(tokens.next().expect("peeked"));
+                        if matches!(tokens.peek(), Some(TokenTree::Punct(colon)) if colon.as_char() == ':') {
+                            out.push(Punct::new(':', Spacing::Joint).into());
+                            out.push(Punct::new(':', Spacing::Alone).into());
+                            out.push(name.clone().into());
+                        }
+                    }
+                }
+            },
+            token => out.push(token),
+        }
+    }
+    out.into_iter().collect()
+}
@@ -0,0 +1,5 @@
+mod util;
+#[cfg(feature = "bug")]
+pub fn buggy() {
+    let x: u8 = util::NAME;
+}
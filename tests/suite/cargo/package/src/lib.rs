@@ -0,0 +1,10 @@
+mod util;
+
+pub fn unrelated() {
+    util::helper();
+}
+
+#[cfg(feature = "bug")]
+pub fn buggy() {
+    let x: u8 = util::NAME;
+}
@@ -0,0 +1,3 @@
+pub fn helper() {}
+
+pub const NAME: &str = "x";
@@ -0,0 +1,3 @@
+pub fn buggy() {
+    let x: u8 = dep::NAME;
+}
@@ -0,0 +1,7 @@
+pub fn unrelated() -> usize {
+    dep::NAME.len()
+}
+
+pub fn buggy() {
+    let x: u8 = dep::NAME;
+}
@@ -0,0 +1,3 @@
+#!/bin/bash
+# run in the copy of the package
+cargo check --offline 2>&1 | grep -q E0308
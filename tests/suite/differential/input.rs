@@ -0,0 +1,16 @@
+fn greet(name: &str) -> String {
+    format!("hello {}", name)
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    let mut sum = 0u8;
+    for &b in data {
+        sum = sum + b;
+    }
+    sum
+}
+
+fn main() {
+    println!("{}", greet("world"));
+    println!("{}", checksum(&[200, 100]));
+}
@@ -0,0 +1,10 @@
+fn checksum(data: &[u8]) -> u8 {
+    let mut sum = 0u8;
+    for &b in data {
+        sum = sum + b;
+    }
+    sum
+}
+fn main() {
+    println!("{}", checksum(&[200, 100]));
+}
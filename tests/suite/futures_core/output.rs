@@ -9,13 +9,9 @@
 #![warn(clippy::all)]
 pub mod future {
     mod future_obj {
-        unsafe impl<'a, T, F> UnsafeFutureObj<'a, T> for &'a mut F
-        where
-            F: Future<Output = T> + Unpin + 'a,
-        {
-            fn into_raw(self) -> *mut (Future<Output = T> + 'a) {
-                unimplemented!()
-            }
+        pub struct LocalFutureObj<T> {}
+        unsafe impl<F> UnsafeFutureObj<'a, T> for &'a mut F {
+            fn into_raw(self) -> *mut (Future<Output = T> + 'a) {}
         }
     }
 }
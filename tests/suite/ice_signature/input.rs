@@ -0,0 +1,17 @@
+pub fn trigger_a() {
+    let y = helper(b"abc");
+    println!("{}", y);
+}
+
+pub struct Unused {
+    field: u32,
+}
+
+pub fn helper(v: &[u8]) -> usize {
+    v.len()
+}
+
+pub fn trigger_b() -> u32 {
+    let x = 1;
+    x + 2
+}
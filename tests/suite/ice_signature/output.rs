@@ -0,0 +1 @@
+pub fn trigger_a() {}
@@ -0,0 +1,34 @@
+#!/bin/bash
+# Pretends to be a rustc that crashes in two different ways
+file="${@: -1}"
+# like rustc, name the crate after the file, with a hash of its name
+crate="$(basename "$file" .rs)"
+hash="$(echo -n "$crate" | md5sum | head -c 16)"
+if grep -q 'trigger_a' "$file"; then
+    line=$(grep -n 'trigger_a' "$file" | head -1 | cut -d: -f1)
+    cat >&2 <<END
+error: internal compiler error: compiler/rustc_middle/src/ty/mod.rs:10:5: unexpected type of DefId(0:$(grep -c '' "$file") ~ $crate[$hash]::trigger_a)
+ --> $file:$line:5
+
+thread 'rustc' panicked at compiler/rustc_middle/src/util/bug.rs:35:26:
+Box<dyn Any>
+note: we would appreciate a bug report
+
+query stack during panic:
+#0 [typeck] type-checking \`<impl at $file:$line:1: $(grep -c "" "$file"):2 (#0)>::trigger_a\`
+#1 [analysis] running analysis passes on this crate
+end of query stack
+END
+    exit 101
+elif grep -q 'trigger_b' "$file"; then
+    cat >&2 <<END
+thread 'rustc' panicked at compiler/rustc_mir_build/src/build/mod.rs:20:9:
+index out of bounds
+note: we would appreciate a bug report
+
+query stack during panic:
+#0 [mir_built] building MIR for \`trigger_b\`
+end of query stack
+END
+    exit 101
+fi
@@ -0,0 +1,2 @@
+// MARKER
+pub fn f() {}
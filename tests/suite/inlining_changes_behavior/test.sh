@@ -0,0 +1,3 @@
+#!/bin/bash
+# comments are lost when the inlined file is printed
+exec grep -q MARKER "$1"
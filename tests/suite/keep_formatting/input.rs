@@ -0,0 +1,17 @@
+// Header comment
+
+/// An unused struct
+struct Unused {
+    a: u32, // field comment
+}
+
+fn unused() -> Unused {
+    Unused { a: 1 }
+}
+
+// MARKER: the function of interest
+pub fn keep(x: u32) -> u32 {
+    let y   =   x + 1;   // odd spacing
+    /* block comment */
+    y * 2
+}
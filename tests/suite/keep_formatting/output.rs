@@ -0,0 +1,4 @@
+// MARKER: the function of interest
+pub fn keep() -> u32 {
+    let y   =   x;   // odd spacing
+}
@@ -0,0 +1,3 @@
+#!/bin/bash
+# the comment is only kept with --keep-formatting
+grep -q MARKER "$1" && grep -q "let y" "$1"
@@ -0,0 +1,10 @@
+fn keep(a: u32) {
+    println!("{}", a);
+}
+
+fn g() {}
+
+fn main() {
+    let a = 42; // the answer
+    drop(a); keep(a); g();
+}
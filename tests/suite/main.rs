@@ -15,25 +15,68 @@
 // You should have received a copy of the GNU General Public License
 // along with rust-reduce.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{env, fs, path::PathBuf, process::Command};
+use std::{env, ffi::OsStr, fs, path::{Path, PathBuf}, process::{Command, Output}, thread, time::Duration};
+
+use quote::ToTokens;
+use rust_reduce::Reducer;
 
 fn run_test(dir: &str) {
+    run_test_with_args(dir, &[])
+}
+
+fn run_test_with_args(dir: &str, args: &[&str]) {
     let mut path = tests_dir();
     path.push(dir);
+    check_output(&path, args, Some(&path.join("test.sh")));
+}
+
+/// Runs the test in `dir` with the shared script that's interesting if the
+/// input compiles to a program that prints 42.
+fn run_program_test(dir: &str, args: &[&str]) {
+    let mut path = tests_dir();
+    path.push(dir);
+    check_output(&path, args, Some(&prints_42()));
+}
+
+fn run_builtin_test(dir: &str, args: &[&str]) {
+    let mut path = tests_dir();
+    path.push(dir);
+    check_output(&path, args, None);
+}
+
+fn check_output(path: &Path, args: &[&str], cmd: Option<&Path>) {
+    let out = run_checked(Command::new(find_rust_reduce())
+        .args(["-1", "-o", "-"])
+        .args(args)
+        .args(cmd)
+        .arg(path.join("input.rs")));
+
+    let expected = fs::read_to_string(path.join("output.rs")).unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+}
 
-    let out = Command::new(find_rust_reduce())
-        .args(&["-1", "-o", "-"])
-        .args(&[path.join("test.sh"), path.join("input.rs")])
-        .output()
-        .unwrap();
+/// Runs `rust-reduce` with `args`, which must succeed.
+fn run_rust_reduce(args: &[&dyn AsRef<OsStr>]) -> Output {
+    run_checked(Command::new(find_rust_reduce()).args(args.iter().map(|arg| arg.as_ref())))
+}
+
+/// Runs `rust-reduce` with `args`, which must fail, and returns the standard
+/// error.
+fn run_rust_reduce_failing(args: &[&dyn AsRef<OsStr>]) -> String {
+    let out = Command::new(find_rust_reduce()).args(args.iter().map(|arg| arg.as_ref())).output().unwrap();
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(!out.status.success(), "{}", stderr);
+    stderr
+}
 
+fn run_checked(command: &mut Command) -> Output {
+    let out = command.output().unwrap();
     if !out.status.success() {
         eprintln!("`rust-reduce` failed with {}", out.status);
-        eprintln!("{}", String::from_utf8(out.stderr).unwrap());
+        eprintln!("{}", String::from_utf8_lossy(&out.stderr));
         panic!("Test failed");
     }
-    let expected = fs::read_to_string(path.join("output.rs")).unwrap();
-    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+    out
 }
 
 macro_rules! tests {
@@ -48,9 +91,423 @@ macro_rules! tests {
 }
 
 tests!(
-    futures_core
+    futures_core,
+    simplify_exprs,
 );
 
+#[test]
+fn futures_core_parallel() {
+    run_test_with_args("futures_core", &["-j", "4"]);
+}
+
+#[test]
+fn remove_arms() {
+    // removing variants would remove the arms along with them
+    run_test_with_args("remove_arms", &["--skip-pass", "remove-variants"]);
+}
+
+#[test]
+fn remove_stmts() {
+    run_program_test("remove_stmts", &["--passes", "remove-stmts"]);
+}
+
+#[test]
+fn prune_items() {
+    run_program_test("prune_items", &["--passes", "prune-items"]);
+}
+
+#[test]
+fn remove_params() {
+    run_program_test("remove_params", &[]);
+}
+
+#[test]
+fn remove_fields() {
+    run_program_test("remove_fields", &[]);
+}
+
+#[test]
+fn remove_variants() {
+    run_program_test("remove_variants", &[]);
+}
+
+#[test]
+fn remove_variants_let() {
+    // the other passes would simplify the `if let` and `while let` away first
+    run_program_test("remove_variants_let", &["--passes", "remove-variants"]);
+}
+
+#[test]
+fn prune_generics() {
+    run_test_with_args("prune_generics", &["--passes", "prune-generics"]);
+}
+
+#[test]
+fn rustc_error() {
+    run_builtin_test("rustc_error", &["--rustc-error", "E0277", "--rustc-flags", "--crate-type lib", "--edition", "2018"]);
+}
+
+#[test]
+fn ice_signature() {
+    let rustc = tests_dir().join("ice_signature").join("rustc.sh");
+    run_builtin_test("ice_signature", &["--ice", "--rustc", rustc.to_str().unwrap()]);
+}
+
+#[test]
+fn differential() {
+    run_builtin_test("differential", &["-j", "4", "--differential", "-C overflow-checks=on", "-C overflow-checks=off"]);
+}
+
+#[test]
+fn keep_formatting() {
+    run_test_with_args("keep_formatting", &["--keep-formatting"]);
+}
+
+#[test]
+fn keep_formatting_comments() {
+    run_program_test("keep_formatting_comments", &["--keep-formatting", "--passes", "remove-stmts"]);
+}
+
+#[test]
+fn module_tree() {
+    let path = tests_dir().join("module_tree");
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("a")).unwrap();
+    for file in ["input.rs", "a.rs", "a/b.rs", "unused.rs"] {
+        fs::copy(path.join(file), dir.path().join(file)).unwrap();
+    }
+
+    run_rust_reduce(&[&"-1", &"--module-tree", &"--rustc-error", &"E0308", &dir.path().join("input.rs")]);
+    for file in ["input.rs", "a.rs", "a/b.rs"] {
+        let expected = fs::read_to_string(path.join("output").join(file)).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join(file)).unwrap(), expected, "{}", file);
+    }
+    // the file of the removed module is deleted, after backing it up
+    assert!(!dir.path().join("unused.rs").exists());
+    for file in ["input.rs", "a.rs", "a/b.rs", "unused.rs"] {
+        let orig = fs::read_to_string(dir.path().join(format!("{}.orig", file))).unwrap();
+        assert_eq!(orig, fs::read_to_string(path.join(file)).unwrap(), "{}", file);
+    }
+}
+
+#[test]
+fn vendor() {
+    let dep = tests_dir().join("vendor").join("dep");
+    run_test_with_args("vendor", &["--vendor", &format!("my-dep={}", dep.display())]);
+}
+
+#[test]
+fn vendor_2015() {
+    let dep = tests_dir().join("vendor_2015").join("dep");
+    run_test_with_args("vendor_2015", &["--vendor", &format!("old-dep={}", dep.display())]);
+}
+
+#[test]
+fn vendor_workspace() {
+    let dep = tests_dir().join("vendor_workspace").join("ws").join("dep");
+    run_test_with_args("vendor_workspace", &["--vendor", &format!("ws-dep={}", dep.display())]);
+
+    // the edition can't be determined without the workspace
+    let path = tests_dir().join("vendor_workspace");
+    let dep = tempfile::tempdir().unwrap();
+    fs::create_dir(dep.path().join("src")).unwrap();
+    fs::copy(path.join("ws/dep/Cargo.toml"), dep.path().join("Cargo.toml")).unwrap();
+    fs::copy(path.join("ws/dep/src/lib.rs"), dep.path().join("src/lib.rs")).unwrap();
+    let vendor = format!("ws-dep={}", dep.path().display());
+    let stderr = run_rust_reduce_failing(&[&"-1", &"-o", &"-", &"--vendor", &vendor, &path.join("test.sh"), &path.join("input.rs")]);
+    assert!(stderr.contains("edition inherited from a workspace that wasn't found"), "{}", stderr);
+}
+
+#[test]
+fn cargo() {
+    let path = tests_dir().join("cargo");
+    let package = tempfile::tempdir().unwrap();
+    fs::create_dir(package.path().join("src")).unwrap();
+    for file in ["Cargo.toml", "src/lib.rs", "src/util.rs"] {
+        fs::copy(path.join("package").join(file), package.path().join(file)).unwrap();
+    }
+
+    run_rust_reduce(&[&"-1", &"--cargo", &package.path(), &path.join("test.sh")]);
+    for file in ["Cargo.toml", "src/lib.rs", "src/util.rs"] {
+        let expected = fs::read_to_string(path.join("output").join(file)).unwrap();
+        assert_eq!(fs::read_to_string(package.path().join(file)).unwrap(), expected, "{}", file);
+    }
+}
+
+#[test]
+fn cargo_output() {
+    let path = tests_dir().join("cargo");
+    let package = tempfile::tempdir().unwrap();
+    fs::create_dir(package.path().join("src")).unwrap();
+    for file in ["Cargo.toml", "src/lib.rs", "src/util.rs"] {
+        fs::copy(path.join("package").join(file), package.path().join(file)).unwrap();
+    }
+    let out_dir = tempfile::tempdir().unwrap();
+    let output = out_dir.path().join("src").join("lib.rs");
+
+    // the reduced Cargo.toml would replace the one in the package
+    let stderr = run_rust_reduce_failing(&[&"-1", &"--cargo", &package.path(), &path.join("test.sh"), &"-o", &output]);
+    assert!(stderr.contains("requires --keep-manifest"), "{}", stderr);
+
+    run_rust_reduce(&[&"-1", &"--keep-manifest", &"--cargo", &package.path(), &path.join("test.sh"), &"-o", &output]);
+    for file in ["src/lib.rs", "src/util.rs"] {
+        let expected = fs::read_to_string(path.join("output").join(file)).unwrap();
+        assert_eq!(fs::read_to_string(out_dir.path().join(file)).unwrap(), expected, "{}", file);
+    }
+    // the package is left alone
+    assert!(!out_dir.path().join("Cargo.toml").exists());
+    for file in ["Cargo.toml", "src/lib.rs", "src/util.rs"] {
+        let original = fs::read_to_string(path.join("package").join(file)).unwrap();
+        assert_eq!(fs::read_to_string(package.path().join(file)).unwrap(), original, "{}", file);
+    }
+}
+
+#[test]
+fn cargo_path_dependency() {
+    let path = tests_dir().join("cargo_path");
+    let dir = tempfile::tempdir().unwrap();
+    for file in ["package/Cargo.toml", "package/src/lib.rs", "dep/Cargo.toml", "dep/src/lib.rs"] {
+        fs::create_dir_all(dir.path().join(file).parent().unwrap()).unwrap();
+        fs::copy(path.join(file), dir.path().join(file)).unwrap();
+    }
+
+    let package = dir.path().join("package");
+    run_rust_reduce(&[&"-1", &"--cargo", &package, &path.join("test.sh")]);
+    // the relative path of the dependency is kept
+    for file in ["Cargo.toml", "src/lib.rs"] {
+        let expected = fs::read_to_string(path.join("output").join(file)).unwrap();
+        assert_eq!(fs::read_to_string(package.join(file)).unwrap(), expected, "{}", file);
+    }
+}
+
+#[test]
+fn cargo_workspace_inheritance() {
+    let path = tests_dir().join("cargo");
+    let package = tempfile::tempdir().unwrap();
+    fs::create_dir(package.path().join("src")).unwrap();
+    fs::copy(path.join("package/src/lib.rs"), package.path().join("src/lib.rs")).unwrap();
+    fs::copy(path.join("package/src/util.rs"), package.path().join("src/util.rs")).unwrap();
+    fs::write(package.path().join("Cargo.toml"), "[package]\nname = \"pkg\"\nversion.workspace = true\n").unwrap();
+
+    let stderr = run_rust_reduce_failing(&[&"-1", &"--cargo", &package.path(), &path.join("test.sh")]);
+    assert!(stderr.contains("inherits from a workspace"), "{}", stderr);
+}
+
+#[test]
+fn inlining_changes_behavior() {
+    let path = tests_dir().join("inlining_changes_behavior");
+    let stderr = run_rust_reduce_failing(&[&"-1", &"-o", &"-", &path.join("test.sh"), &path.join("input.rs")]);
+    assert!(stderr.contains("not after inlining modules"), "{}", stderr);
+}
+
+#[test]
+fn timeout() {
+    let path = tests_dir().join("timeout");
+    let runs = [
+        (&["--timeout", "0.5"][..], "output.rs"),
+        (&["--timeout", "0.5", "--timeout-is-interesting"], "output_interesting.rs"),
+    ];
+    for (args, output) in runs {
+        let marker = tempfile::tempdir().unwrap();
+        let out = run_checked(Command::new(find_rust_reduce())
+            .args(["-1", "-o", "-"])
+            .args(args)
+            .args([path.join("test.sh"), path.join("input.rs")])
+            .env("TIMEOUT_MARKER", marker.path().join("survived")));
+        let expected = fs::read_to_string(path.join(output)).unwrap();
+        assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+        // the commands that timed out were killed along with their children
+        thread::sleep(Duration::from_secs(3));
+        assert!(!marker.path().join("survived").exists());
+    }
+}
+
+#[test]
+fn zero_jobs() {
+    let path = tests_dir().join("remove_stmts");
+    let stderr = run_rust_reduce_failing(&[&"-1", &"-o", &"-", &"-j", &"0", &prints_42(), &path.join("input.rs")]);
+    assert!(stderr.contains("expected a positive integer"), "{}", stderr);
+}
+
+#[test]
+fn max_rounds() {
+    let path = tests_dir().join("remove_stmts");
+    let out = run_rust_reduce(&[&"-1", &"-o", &"-", &"--max-rounds", &"1", &prints_42(), &path.join("input.rs")]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("Round 1") && !stderr.contains("Round 2"), "{}", stderr);
+
+    let stderr = run_rust_reduce_failing(&[&"-1", &"-o", &"-", &"--max-rounds", &"0", &prints_42(), &path.join("input.rs")]);
+    assert!(stderr.contains("expected a positive integer"), "{}", stderr);
+}
+
+#[test]
+fn select_passes() {
+    let path = tests_dir().join("remove_stmts");
+    let out = run_rust_reduce(&[&"-1", &"-o", &"-", &"--passes", &"prune-items,remove-stmts", &"--skip-pass", &"prune-items", &prints_42(), &path.join("input.rs")]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let expected = fs::read_to_string(path.join("output.rs")).unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+    let passes = stderr.lines().filter_map(|line| line.strip_prefix("Running pass ")).collect::<Vec<_>>();
+    assert!(!passes.is_empty() && passes.iter().all(|&pass| pass == "remove-stmts"), "{}", stderr);
+}
+
+#[test]
+fn unknown_pass() {
+    let path = tests_dir().join("remove_stmts");
+    for option in ["--passes", "--skip-pass"] {
+        let stderr = run_rust_reduce_failing(&[&"-1", &"-o", &"-", &option, &"no-such-pass", &prints_42(), &path.join("input.rs")]);
+        assert!(stderr.contains("unknown pass `no-such-pass`"), "{}", stderr);
+    }
+}
+
+#[test]
+fn list_passes() {
+    let out = run_rust_reduce(&[&"--list-passes"]);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let names = stdout.lines().filter_map(|line| line.split_whitespace().next()).collect::<Vec<_>>();
+    let expected = rust_reduce::transforms::ALL.iter().map(|pass| pass.name()).collect::<Vec<_>>();
+    assert_eq!(names, expected);
+}
+
+/// Runs the remove_stmts test with `--cache`, returns the number of tests
+/// run and the standard error.
+fn run_cached(cache: &Path) -> (usize, String) {
+    let path = tests_dir().join("remove_stmts");
+    let out = run_rust_reduce(&[&"-1", &"-o", &"-", &"--cache", &cache, &prints_42(), &path.join("input.rs")]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let expected = fs::read_to_string(path.join("output.rs")).unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+    let tests = stderr.lines()
+        .find_map(|line| line.strip_prefix("Ran ")?.split(' ').next()?.parse().ok())
+        .unwrap_or_else(|| panic!("no test count: {}", stderr));
+    (tests, stderr)
+}
+
+#[test]
+fn cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("cache");
+    let (tests, _) = run_cached(&cache);
+    assert!(tests > 0);
+    // every outcome is known the second time
+    let (tests, _) = run_cached(&cache);
+    assert_eq!(tests, 0);
+}
+
+#[test]
+fn cache_truncated() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("cache");
+    run_cached(&cache);
+
+    // as if the previous run was interrupted while writing the last entry
+    let contents = fs::read(&cache).unwrap();
+    fs::write(&cache, &contents[..contents.len() - 10]).unwrap();
+    let (tests, stderr) = run_cached(&cache);
+    assert!(stderr.contains("ignoring invalid entries"), "{}", stderr);
+    assert_eq!(tests, 1);
+    // the bad entry was replaced
+    let (tests, _) = run_cached(&cache);
+    assert_eq!(tests, 0);
+}
+
+#[test]
+fn library() {
+    let file = syn::parse_str(r#"
+        fn unused() {}
+        /// Docs.
+        pub fn keep<T: Copy>(x: u32) -> u32 {
+            let y = x + 1;
+            y * 2
+        }
+        struct Unused<T>(T);
+    "#).unwrap();
+
+    let reduced = Reducer::new(file).reduce(&mut |file: &syn::File| {
+        file.items.iter().any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "keep"))
+    });
+
+    let expected: syn::File = syn::parse_str("pub fn keep() -> u32 {}").unwrap();
+    assert_eq!(reduced.into_token_stream().to_string(), expected.into_token_stream().to_string());
+}
+
+#[test]
+fn reduce_manifest() {
+    let manifest = r#"[package]
+name = "pkg"
+version = "0.1.0"
+
+[dependencies]
+a = "1"
+b = "1"
+
+[target.'cfg(unix)'.dependencies]
+c = "1"
+
+[dev-dependencies]
+d = { version = "1", features = ["x"] }
+
+[profile.dev]
+debug = false
+"#;
+
+    let mut tested = vec![];
+    let reduced = rust_reduce::manifest::reduce_manifest(manifest, 2, |candidates| {
+        tested.extend(candidates.iter().cloned());
+        candidates.iter().position(|candidate| candidate.contains("b = \"1\""))
+    }).unwrap();
+
+    // dependencies and the tables that end up empty are removed
+    assert_eq!(reduced, "[package]\nname = \"pkg\"\nversion = \"0.1.0\"\n\n[dependencies]\nb = \"1\"\n");
+    // the target-specific dependency is removed on its own
+    assert!(tested.iter().any(|candidate| !candidate.contains("cfg(unix)") && candidate.contains("debug = false")), "{:#?}", tested);
+}
+
+#[test]
+fn reduce_manifest_optional_dependencies() {
+    let manifest = r#"[package]
+name = "pkg"
+version = "0.1.0"
+
+[dependencies]
+bar = { version = "1", optional = true }
+baz = { version = "1", optional = true }
+keep = "1"
+
+[features]
+foo = ["dep:bar"]
+extra = ["baz/x"]
+"#;
+
+    // like cargo, reject features that refer to missing dependencies
+    let reduced = rust_reduce::manifest::reduce_manifest(manifest, 1, |candidates| {
+        candidates.iter().position(|candidate| {
+            let valid = |feature: &str, dependency: &str| !candidate.contains(feature) || candidate.contains(dependency);
+            candidate.contains("keep = ") && valid("\"dep:bar\"", "bar = {") && valid("\"baz/x\"", "baz = {")
+        })
+    }).unwrap();
+
+    // the dependencies are removed after the features that enable them
+    assert_eq!(reduced, "[package]\nname = \"pkg\"\nversion = \"0.1.0\"\n\n[dependencies]\nkeep = \"1\"\n");
+}
+
+#[test]
+fn prune_items_in_chunks() {
+    let source = (0..64).map(|i| format!("fn f{}() {{}}", i)).collect::<String>();
+    let mut file = syn::parse_str(&source).unwrap();
+
+    let mut tests = 0;
+    rust_reduce::transforms::prune_items::prune_items(&mut file, &mut |file: &syn::File| {
+        tests += 1;
+        file.items.iter().any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "f42"))
+    });
+
+    let expected: syn::File = syn::parse_str("fn f42() {}").unwrap();
+    assert_eq!(file.into_token_stream().to_string(), expected.into_token_stream().to_string());
+    // removing one item at a time would take 64 tests
+    assert!(tests < 32, "{} tests", tests);
+}
+
 fn find_rust_reduce() -> PathBuf {
     let mut path = env::current_exe().unwrap();
     path.pop();
@@ -62,6 +519,10 @@ fn find_rust_reduce() -> PathBuf {
     path
 }
 
+fn prints_42() -> PathBuf {
+    tests_dir().join("prints_42.sh")
+}
+
 fn tests_dir() -> PathBuf {
     let mut path = PathBuf::from(file!());
     path.pop();
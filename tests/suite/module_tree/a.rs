@@ -0,0 +1,4 @@
+//! Module a
+pub mod b;
+
+pub fn g() {}
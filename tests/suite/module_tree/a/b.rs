@@ -0,0 +1,5 @@
+pub fn f() {
+    let x: u8 = "no";
+}
+
+pub fn h() {}
@@ -0,0 +1,7 @@
+//! Root docs
+mod a;
+mod unused;
+
+fn main() {
+    a::b::f();
+}
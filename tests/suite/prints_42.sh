@@ -0,0 +1,5 @@
+#!/bin/bash
+# interesting if the input compiles to a program that prints 42
+dir=$(mktemp -d)
+trap 'rm -rf "$dir"' EXIT
+rustc -A warnings -o "$dir/main" "$1" 2>/dev/null && [ "$("$dir/main")" = 42 ]
@@ -0,0 +1,17 @@
+use std::fmt::{Debug, Display};
+
+pub struct Labeled<'a, T: Display + Debug> {
+    pub label: &'a str,
+    pub value: T,
+}
+
+impl<'a, T: Display + Debug + Clone> Labeled<'a, T> {
+    pub fn show<'c, V, const M: usize>(&self, extra: &'c str) -> String where V: Clone, T: Sized {
+        let len: u32 = "not a number";
+        format!("{}: {} {}", self.label, self.value, extra)
+    }
+}
+
+pub fn helper<'x, A: Default, const K: usize>() -> u32 where A: Copy {
+    0
+}
@@ -0,0 +1,14 @@
+use std::fmt::{Debug, Display};
+pub struct Labeled<'a, T> {
+    pub label: &'a str,
+    pub value: T,
+}
+impl<'a, T: Display> Labeled<'a, T> {
+    pub fn show<'c>(&self, extra: &'c str) -> String {
+        let len: u32 = "not a number";
+        format!("{}: {} {}", self.label, self.value, extra)
+    }
+}
+pub fn helper() -> u32 {
+    0
+}
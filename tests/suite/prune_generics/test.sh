@@ -0,0 +1,4 @@
+#!/bin/bash
+# interesting if the only error is the type mismatch
+out=$(rustc --crate-type lib --edition 2018 -o /dev/null "$1" 2>&1)
+[ "$(grep -c '^error\[' <<<"$out")" = 1 ] && grep -q '^error\[E0308\]' <<<"$out"
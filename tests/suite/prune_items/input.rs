@@ -0,0 +1,27 @@
+fn main() {
+    use std::fmt::Write;
+
+    struct Unused;
+
+    impl Unused {
+        fn describe(&self) -> &'static str {
+            "unused"
+        }
+    }
+
+    fn answer() -> u32 {
+        const BASE: u32 = 40;
+        const UNUSED: u32 = 1;
+        fn offset() -> u32 {
+            2
+        }
+        fn unused_helper() {}
+        BASE + offset()
+    }
+
+    let value = {
+        fn unused_in_block() {}
+        answer()
+    };
+    println!("{}", value);
+}
@@ -0,0 +1,11 @@
+fn main() {
+    fn answer() -> u32 {
+        const BASE: u32 = 40;
+        fn offset() -> u32 {
+            2
+        }
+        BASE + offset()
+    }
+    let value = { answer() };
+    println!("{}", value);
+}
@@ -0,0 +1,9 @@
+pub enum E { A, B(u32), C, D }
+pub fn f(e: E) -> u32 {
+    match e {
+        E::A => 1,
+        E::B(x) => { let q = 1; x }
+        E::C => { 3 }
+        E::D => 4
+    }
+}
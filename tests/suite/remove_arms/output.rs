@@ -0,0 +1,15 @@
+pub enum E {
+    A,
+    B(u32),
+    C,
+    D,
+}
+pub fn f(e: E) -> u32 {
+    match e {
+        E::B(x) => {
+            let q = 1;
+            x
+        }
+        _ => unimplemented!(),
+    }
+}
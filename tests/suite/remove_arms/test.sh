@@ -0,0 +1,2 @@
+#!/bin/bash
+out=$(rustc --crate-type lib -o /dev/null "$1" 2>&1) && exec grep -q 'unused variable: `q`' <<<"$out"
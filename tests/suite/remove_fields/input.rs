@@ -0,0 +1,31 @@
+struct Config {
+    name: &'static str,
+    value: u32,
+    verbose: bool,
+}
+
+struct Pair(bool, u32, &'static str);
+
+impl Pair {
+    fn value(&self) -> u32 {
+        self.1
+    }
+}
+
+impl Config {
+    fn new(value: u32) -> Self {
+        Self { name: "config", value, verbose: false }
+    }
+}
+
+fn main() {
+    let Config { name, value, .. } = Config::new(40);
+    let pair = Pair(true, 2, name);
+    let Pair(_, _, _) = pair;
+    if let Pair(.., tag) = pair {
+        let _ = tag;
+    }
+    let copy: &Pair = &pair;
+    let total = value + pair.value() * copy.1 / pair.1;
+    println!("{}", total);
+}
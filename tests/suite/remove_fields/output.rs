@@ -0,0 +1,15 @@
+struct Config {
+    value: u32,
+}
+struct Pair(u32);
+impl Config {
+    fn new(value: u32) -> Self {
+        Self { value }
+    }
+}
+fn main() {
+    let Config { value, .. } = Config::new(40);
+    let pair = Pair(2);
+    let total = value + pair.0;
+    println!("{}", total);
+}
@@ -0,0 +1,21 @@
+struct S(u32);
+
+impl S {
+    fn method(&self, unused: &str, used: u32) -> u32 {
+        self.0 + used
+    }
+
+    fn assoc(a: u32, b: bool) -> u32 {
+        a
+    }
+}
+
+fn helper(x: u32, y: &str, z: bool) -> u32 {
+    x * 2
+}
+
+fn main() {
+    let s = S(1);
+    let total = helper(10, "a", true) + s.method("b", 3) + S::assoc(4, false) + S::method(&s, "c", 13);
+    println!("{}", total);
+}
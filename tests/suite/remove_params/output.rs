@@ -0,0 +1,14 @@
+struct S(u32);
+impl S {
+    fn method(&self, used: u32) -> u32 {
+        self.0 + used
+    }
+}
+fn helper(x: u32) -> u32 {
+    x * 2
+}
+fn main() {
+    let s = S(1);
+    let total = helper(10) + s.method(3) + 4 + S::method(&s, 13);
+    println!("{}", total);
+}
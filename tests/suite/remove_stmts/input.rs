@@ -0,0 +1,17 @@
+fn main() {
+    let mut total = 0;
+    let unused = vec![1, 2, 3];
+    eprintln!("starting");
+    for i in 0..7 {
+        let square = i * i;
+        eprintln!("{}", square);
+        total += i;
+    }
+    {
+        let scratch = String::from("scratch");
+        eprintln!("{}", scratch);
+        total += 21;
+    }
+    drop(unused);
+    println!("{}", total);
+}
@@ -0,0 +1,10 @@
+fn main() {
+    let mut total = 0;
+    for i in 0..7 {
+        total += i;
+    }
+    {
+        total += 21;
+    }
+    println!("{}", total);
+}
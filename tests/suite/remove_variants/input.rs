@@ -0,0 +1,38 @@
+enum Error {
+    NotFound(&'static str),
+    Timeout { seconds: u32 },
+    Invalid,
+    Overflow(u32),
+}
+
+impl Error {
+    fn code(&self) -> u32 {
+        match self {
+            Self::NotFound(_) | Self::Invalid => 1,
+            Self::Timeout { seconds } => *seconds,
+            Self::Overflow(n) => *n,
+        }
+    }
+}
+
+fn check(value: u32) -> Result<u32, Error> {
+    if value > 100 {
+        return Err(Error::Timeout { seconds: value });
+    }
+    if value == 0 {
+        return Err(Error::Invalid);
+    }
+    if value == 1 {
+        return Err(Error::NotFound("one"));
+    }
+    Err(Error::Overflow(value))
+}
+
+fn main() {
+    let code = match check(42) {
+        Ok(_) => 0,
+        Err(Error::Invalid) => 2,
+        Err(e) => e.code(),
+    };
+    println!("{}", code);
+}
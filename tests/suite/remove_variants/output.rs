@@ -0,0 +1,20 @@
+enum Error {
+    Overflow(u32),
+}
+impl Error {
+    fn code(&self) -> u32 {
+        match self {
+            Self::Overflow(n) => *n,
+        }
+    }
+}
+fn check(value: u32) -> Result<u32, Error> {
+    Err(Error::Overflow(value))
+}
+fn main() {
+    let code = match check(42) {
+        Err(e) => e.code(),
+        _ => unimplemented!(),
+    };
+    println!("{}", code);
+}
@@ -0,0 +1,19 @@
+enum Event {
+    Click { x: u32 },
+    Scroll(i32),
+    Quit,
+}
+
+fn main() {
+    let mut events = vec![Event::Click { x: 40 }];
+    let mut total = 0;
+    while let Some(Event::Scroll(_)) = events.last() {
+        events.pop();
+    }
+    if let Some(Event::Quit) = events.last() {
+        total = 1;
+    } else if let Some(Event::Click { x }) = events.last() {
+        total = *x + 2;
+    }
+    println!("{}", total);
+}
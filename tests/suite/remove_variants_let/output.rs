@@ -0,0 +1,12 @@
+enum Event {
+    Click { x: u32 },
+}
+fn main() {
+    let mut events = vec![Event::Click { x: 40 }];
+    let mut total = 0;
+    {}
+    if let Some(Event::Click { x }) = events.last() {
+        total = *x + 2;
+    }
+    println!("{}", total);
+}
@@ -0,0 +1,6 @@
+use std::fmt::Display;
+pub struct Foo { a: u32 }
+pub fn show<T: Display>(t: T) -> String { format!("{}", t) }
+pub fn helper(x: u32) -> u32 { x * 2 }
+pub fn f() { let foo = Foo { a: helper(1) }; show(foo); }
+pub mod m { pub fn g() -> i32 { 3 } }
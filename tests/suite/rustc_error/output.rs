@@ -0,0 +1,7 @@
+use std::fmt::Display;
+pub struct Foo {}
+pub fn show<T: Display>(t: T) -> String {}
+pub fn f() {
+    let foo = Foo {};
+    show(foo);
+}
@@ -0,0 +1,4 @@
+pub fn f(a: u32) -> u32 {
+    let c = if a > 3 { (a + 2) * (a - ("x" + 1)) } else { a.max(7) };
+    c
+}
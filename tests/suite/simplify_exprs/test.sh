@@ -0,0 +1,2 @@
+#!/bin/bash
+rustc --crate-type lib -o /dev/null "$1" |& exec grep -q 'E0369'
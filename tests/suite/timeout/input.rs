@@ -0,0 +1,5 @@
+fn keep() {}
+
+fn slow() {}
+
+fn other() {}
@@ -0,0 +1,7 @@
+#!/bin/bash
+grep -q 'fn keep' "$1" || exit 1
+# hang if `slow` was removed. the subshell survives unless the whole process
+# group is killed
+if ! grep -q 'fn slow' "$1"; then
+    (sleep 2; touch "$TIMEOUT_MARKER")
+fi
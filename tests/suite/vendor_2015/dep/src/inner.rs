@@ -0,0 +1,5 @@
+pub struct Thing(pub u32);
+
+pub fn new() -> Thing {
+    Thing(::std::mem::size_of::<u32>() as u32)
+}
@@ -0,0 +1,16 @@
+mod inner;
+
+use inner::Thing;
+
+pub fn make() -> ::std::boxed::Box<Thing> {
+    ::std::boxed::Box::new(::inner::new())
+}
+
+pub fn unused() -> ::std::string::String {
+    ::std::string::String::new()
+}
+
+#[macro_export]
+macro_rules! make_thing {
+    () => { $crate::make() };
+}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate old_dep;
+
+pub fn unrelated() -> usize {
+    old_dep::unused().len()
+}
+
+pub fn f() -> u32 {
+    make_thing!()
+}
@@ -0,0 +1,19 @@
+#[macro_use]
+pub mod old_dep {
+    mod inner {
+        pub struct Thing();
+    }
+    use crate::old_dep::inner::Thing;
+    pub fn make() -> ::std::boxed::Box<Thing> {
+        unimplemented!()
+    }
+    #[macro_export]
+    macro_rules! make_thing {
+        () => {
+            $crate::old_dep::make()
+        };
+    }
+}
+pub fn f() -> u32 {
+    make_thing!()
+}
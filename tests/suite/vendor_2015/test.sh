@@ -0,0 +1,8 @@
+#!/bin/bash
+# the original input needs the dependency, the vendored input doesn't
+dir=$(mktemp -d)
+trap 'rm -rf "$dir"' EXIT
+rustc --edition 2015 --crate-type lib --crate-name old_dep --out-dir "$dir" "$(dirname "$0")/dep/src/lib.rs" 2>/dev/null
+out=$(rustc --edition 2018 --crate-type lib --extern old_dep="$dir/libold_dep.rlib" --out-dir "$dir" "$1" 2>&1)
+# no other errors, e.g. from rewriting paths in the dependency
+echo "$out" | grep -q 'expected `u32`, found `Box<' && echo "$out" | grep -q 'due to 1 previous error'
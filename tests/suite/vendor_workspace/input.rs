@@ -0,0 +1,5 @@
+extern crate ws_dep;
+
+pub fn f() {
+    let x: u32 = ws_dep::outer::make();
+}
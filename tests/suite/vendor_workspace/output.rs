@@ -0,0 +1,14 @@
+pub mod ws_dep {
+    pub mod outer {
+        pub mod thing {
+            pub struct Thing();
+        }
+        use thing::Thing;
+        pub fn make() -> Thing {
+            unimplemented!()
+        }
+    }
+}
+pub fn f() {
+    let x: u32 = crate::ws_dep::outer::make();
+}
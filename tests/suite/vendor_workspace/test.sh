@@ -0,0 +1,8 @@
+#!/bin/bash
+# the original input needs the dependency, the vendored input doesn't
+dir=$(mktemp -d)
+trap 'rm -rf "$dir"' EXIT
+rustc --edition 2018 --crate-type lib --crate-name ws_dep --out-dir "$dir" "$(dirname "$0")/ws/dep/src/lib.rs" 2>/dev/null
+out=$(rustc --edition 2018 --crate-type lib --extern ws_dep="$dir/libws_dep.rlib" --out-dir "$dir" "$1" 2>&1)
+# no other errors, e.g. from rewriting paths in the dependency
+echo "$out" | grep -q 'expected `u32`, found `Thing`' && echo "$out" | grep -q 'due to 1 previous error'
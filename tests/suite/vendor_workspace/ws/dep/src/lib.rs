@@ -0,0 +1,12 @@
+pub mod outer {
+    pub mod thing {
+        pub struct Thing(pub u32);
+    }
+
+    // relative to `outer` since the 2018 edition
+    use thing::Thing;
+
+    pub fn make() -> Thing {
+        Thing(1)
+    }
+}